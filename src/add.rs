@@ -0,0 +1,172 @@
+use regex::Regex;
+use reqwest;
+
+use pipfile::{PackageInfo, PackageSource};
+use pypi::get_package_data;
+use semver_utils::{is_prerelease, normalize_and_parse_version_string};
+use tags::TargetPython;
+use version_req::PackageVersionReq;
+use errors::*;
+
+/// The specifier `add` writes for a package given without one.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SpecifierStyle {
+    /// A compatible release clause on the latest version, eg. `~=2.31`.
+    Compatible,
+    /// Any version, `*`.
+    Any,
+}
+impl SpecifierStyle {
+    pub fn parse(style: &str) -> Result<SpecifierStyle> {
+        match style {
+            "compatible" => Ok(SpecifierStyle::Compatible),
+            "any" => Ok(SpecifierStyle::Any),
+            _ => bail!("unknown specifier style `{}`", style),
+        }
+    }
+}
+
+/// Splits a command line argument such as `requests[socks]>=2.18` into the
+/// package name and its Pipfile entry. The entry's version is left empty
+/// when the argument doesn't give a specifier.
+pub fn parse_package_argument(argument: &str) -> Result<(String, PackageInfo)> {
+    lazy_static! {
+        static ref ARGUMENT_RE: Regex = Regex::new(
+            r"^\s*([A-Za-z0-9][A-Za-z0-9._-]*)\s*(?:\[([^\]]*)\])?\s*([^;]*?)\s*(?:;\s*(.*?))?\s*$"
+        ).unwrap();
+    }
+    let captures = ARGUMENT_RE
+        .captures(argument)
+        .ok_or_else(|| ErrorKind::PackageNameRegexFailed(argument.to_owned()))?;
+    let name = captures[1].to_owned();
+    let version = captures[3].to_owned();
+    // Fail now rather than after the Pipfile has been written
+    PackageVersionReq::from_specifier(&name, &version)?;
+    let extras = captures
+        .get(2)
+        .map(|extras| {
+            extras
+                .as_str()
+                .split(',')
+                .map(|x| x.trim().to_owned())
+                .filter(|x| x != "")
+                .collect()
+        })
+        .unwrap_or_else(Vec::new);
+    let markers = captures
+        .get(4)
+        .map(|x| x.as_str().to_owned())
+        .filter(|x| x != "");
+    Ok((
+        name,
+        PackageInfo {
+            source: PackageSource::Index {
+                version: version,
+                index: None,
+            },
+            extras: extras,
+            markers: markers,
+        },
+    ))
+}
+
+/// `~=major.minor` of `version_string`, eg. `~=2.31` for `2.31.0`. A
+/// pre-release is kept in full, eg. `~=2.0b1`, since `~=2.0` would exclude it.
+pub fn compatible_specifier(version_string: &str) -> String {
+    lazy_static! {
+        static ref RELEASE_RE: Regex = Regex::new(r"^v?(\d+)(?:\.(\d+))?").unwrap();
+    }
+    let is_prerelease = normalize_and_parse_version_string(version_string)
+        .map(|version| is_prerelease(&version))
+        .unwrap_or(false);
+    match RELEASE_RE.captures(version_string) {
+        // `~=` needs at least two release components
+        Some(ref captures) if is_prerelease && captures.get(2).is_none() => {
+            format!(">={}", version_string)
+        }
+        Some(_) if is_prerelease => format!("~={}", version_string),
+        Some(captures) => format!(
+            "~={}.{}",
+            &captures[1],
+            captures.get(2).map_or("0", |x| x.as_str())
+        ),
+        None => "*".to_owned(),
+    }
+}
+
+/// Fills in the specifier of an entry parsed by `parse_package_argument`
+/// that didn't have one, looking up the latest version installable on
+/// `target` when `style` needs it.
+pub fn infer_specifier(
+    client: &reqwest::Client,
+    target: &TargetPython,
+    name: &str,
+    package_info: &mut PackageInfo,
+    style: SpecifierStyle,
+    allow_prereleases: bool,
+) -> Result<()> {
+    if let PackageSource::Index {
+        ref mut version, ..
+    } = package_info.source
+    {
+        if *version != "" {
+            return Ok(());
+        }
+        *version = match style {
+            SpecifierStyle::Any => "*".to_owned(),
+            SpecifierStyle::Compatible => {
+                let package = get_package_data(client, name)?;
+                let latest = package.best_version_matching(
+                    &[PackageVersionReq::from_specifier(name, "*")?],
+                    target,
+                    allow_prereleases,
+                )?;
+                compatible_specifier(package
+                    .version_string(&latest)
+                    .unwrap_or(&latest.to_string()))
+            }
+        };
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use add::*;
+
+    #[test]
+    fn parse_argument_with_extras_and_specifier() {
+        let (name, package_info) =
+            parse_package_argument("requests[socks, security]>=2.18; python_version >= '3'")
+                .unwrap();
+
+        assert_eq!(name, "requests");
+        assert_eq!(package_info.version_spec(), Some(">=2.18"));
+        assert_eq!(package_info.extras, vec!["socks", "security"]);
+        assert_eq!(package_info.markers, Some("python_version >= '3'".to_owned()));
+    }
+
+    #[test]
+    fn parse_bare_argument() {
+        let (name, package_info) = parse_package_argument("zope.interface").unwrap();
+
+        assert_eq!(name, "zope.interface");
+        assert_eq!(package_info.version_spec(), Some(""));
+        assert!(package_info.extras.is_empty());
+        assert!(parse_package_argument("requests>=two").is_err());
+    }
+
+    #[test]
+    fn compatible_specifiers() {
+        assert_eq!(compatible_specifier("2.31.0"), "~=2.31");
+        assert_eq!(compatible_specifier("2017.4.17"), "~=2017.4");
+        assert_eq!(compatible_specifier("5"), "~=5.0");
+        assert_eq!(compatible_specifier("2.0b1"), "~=2.0b1");
+        assert_eq!(compatible_specifier("3b2"), ">=3b2");
+        assert_eq!(compatible_specifier("1.0.post1"), "~=1.0");
+        let version = normalize_and_parse_version_string("2.0b1").unwrap();
+        assert!(PackageVersionReq::from_specifier("six", &compatible_specifier("2.0b1"))
+            .unwrap()
+            .matches(&version));
+    }
+}
@@ -0,0 +1,244 @@
+use std::env;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use zip::read::ZipFile;
+
+use errors::*;
+
+/// How much an archive may contain before it's treated as hostile, so that
+/// eg. a gzip bomb fails instead of exhausting memory or disk. See
+/// `from_env` for raising them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArchiveLimits {
+    pub max_entries: usize,
+    /// The most uncompressed bytes any one entry may hold.
+    pub max_entry_size: u64,
+    /// The most uncompressed bytes all entries together may hold.
+    pub max_total_size: u64,
+}
+impl Default for ArchiveLimits {
+    fn default() -> ArchiveLimits {
+        ArchiveLimits {
+            max_entries: 50_000,
+            max_entry_size: 512 * 1024 * 1024,
+            max_total_size: 2 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+impl ArchiveLimits {
+    /// The default limits, with any overridden by `PIPENV_MAX_ARCHIVE_ENTRIES`,
+    /// `PIPENV_MAX_ARCHIVE_ENTRY_SIZE` or `PIPENV_MAX_ARCHIVE_SIZE`, the
+    /// sizes being in bytes.
+    pub fn from_env() -> Result<ArchiveLimits> {
+        let var = |name: &str| -> Result<Option<u64>> {
+            match env::var(name) {
+                Ok(ref value) if value.trim() != "" => value
+                    .trim()
+                    .parse()
+                    .map(Some)
+                    .chain_err(|| format!("{} isn't a number: `{}`", name, value)),
+                _ => Ok(None),
+            }
+        };
+        let mut limits = ArchiveLimits::default();
+        if let Some(max_entries) = var("PIPENV_MAX_ARCHIVE_ENTRIES")? {
+            limits.max_entries = max_entries as usize;
+        }
+        if let Some(max_entry_size) = var("PIPENV_MAX_ARCHIVE_ENTRY_SIZE")? {
+            limits.max_entry_size = max_entry_size;
+        }
+        if let Some(max_total_size) = var("PIPENV_MAX_ARCHIVE_SIZE")? {
+            limits.max_total_size = max_total_size;
+        }
+        Ok(limits)
+    }
+}
+
+/// Checks the entries of one archive as they're visited. Every name is
+/// checked for traversal, and sizes are enforced both against what entries
+/// declare and against what is actually read, since headers can lie.
+pub struct ArchiveGuard {
+    limits: ArchiveLimits,
+    entries: usize,
+    total_size: u64,
+    /// What the headers of every entry visited so far declare, read or not.
+    declared_total_size: u64,
+}
+impl ArchiveGuard {
+    pub fn new(limits: &ArchiveLimits) -> ArchiveGuard {
+        ArchiveGuard {
+            limits: *limits,
+            entries: 0,
+            total_size: 0,
+            declared_total_size: 0,
+        }
+    }
+
+    /// Counts the entry `name` whose header declares `declared_size` bytes,
+    /// returning its path relative to wherever the archive is unpacked. The
+    /// declared size counts toward the total even if the entry is skipped.
+    pub fn entry(&mut self, name: &str, declared_size: u64) -> Result<PathBuf> {
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            bail!(ErrorKind::ArchiveTooManyEntries(self.limits.max_entries));
+        }
+        if declared_size > self.limits.max_entry_size {
+            bail!(ErrorKind::ArchiveEntryTooLarge(
+                name.to_owned(),
+                self.limits.max_entry_size
+            ));
+        }
+        self.declared_total_size += declared_size;
+        if self.declared_total_size > self.limits.max_total_size {
+            bail!(ErrorKind::ArchiveTooLarge(self.limits.max_total_size));
+        }
+        safe_path(name)
+    }
+
+    /// Checks that the link `name` to `target` stays inside the archive.
+    /// Symbolic link targets are relative to the link's directory, hard link
+    /// targets to the root of the archive.
+    pub fn link(&self, name: &str, target: &Path, hard_link: bool) -> Result<()> {
+        let unsafe_link = || ErrorKind::UnsafeArchiveLink(name.to_owned(), target.to_owned());
+        let mut resolved = vec![];
+        if !hard_link {
+            resolved = safe_path(name)?.components().map(|x| x.as_os_str().to_owned()).collect();
+            resolved.pop();
+        }
+        for component in target.components() {
+            match component {
+                Component::Normal(x) => resolved.push(x.to_owned()),
+                Component::CurDir => (),
+                Component::ParentDir => {
+                    resolved.pop().ok_or_else(unsafe_link)?;
+                }
+                Component::RootDir | Component::Prefix(_) => bail!(unsafe_link()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the contents of the entry `name`, failing as soon as more is
+    /// read than the limits allow.
+    pub fn read<R: Read>(&mut self, name: &str, reader: R) -> Result<Vec<u8>> {
+        let remaining = self.limits.max_total_size - self.total_size;
+        let limit = self.limits.max_entry_size.min(remaining);
+        let mut bytes = vec![];
+        reader.take(limit + 1).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 > limit {
+            if self.limits.max_entry_size <= remaining {
+                bail!(ErrorKind::ArchiveEntryTooLarge(name.to_owned(), limit));
+            }
+            bail!(ErrorKind::ArchiveTooLarge(self.limits.max_total_size));
+        }
+        self.total_size += bytes.len() as u64;
+        Ok(bytes)
+    }
+
+    pub fn read_to_string<R: Read>(&mut self, name: &str, reader: R) -> Result<String> {
+        String::from_utf8(self.read(name, reader)?)
+            .chain_err(|| format!("{} in archive isn't valid utf-8", name))
+    }
+}
+
+/// The archive entry `name` as a relative path, rejecting absolute paths and
+/// any that climb out of the directory they'd be unpacked into.
+pub fn safe_path(name: &str) -> Result<PathBuf> {
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(x) => path.push(x),
+            Component::CurDir => (),
+            _ => bail!(ErrorKind::UnsafeArchivePath(name.to_owned())),
+        }
+    }
+    if path.as_os_str().is_empty() {
+        bail!(ErrorKind::UnsafeArchivePath(name.to_owned()));
+    }
+    Ok(path)
+}
+
+/// Whether a zip entry is a symbolic link, which zip stores as a file
+/// holding the link target with a unix mode of `S_IFLNK`.
+pub fn is_zip_symlink(file: &ZipFile) -> bool {
+    file.unix_mode().map_or(false, |mode| mode & 0o170000 == 0o120000)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use archive::*;
+
+    fn limits(max_entries: usize, max_entry_size: u64, max_total_size: u64) -> ArchiveLimits {
+        ArchiveLimits {
+            max_entries: max_entries,
+            max_entry_size: max_entry_size,
+            max_total_size: max_total_size,
+        }
+    }
+
+    #[test]
+    fn rejects_traversal() {
+        assert_eq!(safe_path("./pkg-1.0/setup.py").unwrap(), Path::new("pkg-1.0/setup.py"));
+        for name in &["../evil.py", "pkg/../../evil.py", "/etc/passwd", "", "."] {
+            match *safe_path(name).unwrap_err().kind() {
+                ErrorKind::UnsafeArchivePath(_) => (),
+                ref kind => panic!("unexpected error {:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_escaping_links() {
+        let guard = ArchiveGuard::new(&ArchiveLimits::default());
+
+        assert!(guard.link("pkg/docs/link", Path::new("../README"), false).is_ok());
+        assert!(guard.link("pkg/link", Path::new("../../etc/passwd"), false).is_err());
+        assert!(guard.link("pkg/link", Path::new("/etc/passwd"), false).is_err());
+        assert!(guard.link("pkg/docs/link", Path::new("pkg/README"), true).is_ok());
+        assert!(guard.link("pkg/docs/link", Path::new("../README"), true).is_err());
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let mut guard = ArchiveGuard::new(&limits(2, 4, 6));
+
+        assert!(guard.entry("a", 4).is_ok());
+        assert_eq!(guard.read("a", &b"1234"[..]).unwrap(), b"1234");
+        match *guard.read("b", &b"123"[..]).unwrap_err().kind() {
+            ErrorKind::ArchiveTooLarge(6) => (),
+            ref kind => panic!("unexpected error {:?}", kind),
+        }
+        assert!(guard.entry("b", 1).is_ok());
+        match *guard.entry("c", 0).unwrap_err().kind() {
+            ErrorKind::ArchiveTooManyEntries(2) => (),
+            ref kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn enforces_entry_size_even_when_declared_smaller() {
+        let mut guard = ArchiveGuard::new(&limits(2, 4, 100));
+
+        assert!(guard.entry("big", 5).is_err());
+        assert!(guard.entry("bomb", 1).is_ok());
+        match *guard.read("bomb", &b"12345"[..]).unwrap_err().kind() {
+            ErrorKind::ArchiveEntryTooLarge(ref name, 4) => assert_eq!(name, "bomb"),
+            ref kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn counts_skipped_entries_toward_total() {
+        let mut guard = ArchiveGuard::new(&limits(10, 4, 6));
+
+        assert!(guard.entry("a", 4).is_ok());
+        assert!(guard.entry("b", 2).is_ok());
+        assert_eq!(guard.read("b", &b"12"[..]).unwrap(), b"12");
+        match *guard.entry("c", 1).unwrap_err().kind() {
+            ErrorKind::ArchiveTooLarge(6) => (),
+            ref kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use regex::Regex;
+
+use errors::*;
+
+/// One `name = module:attribute [extras]` line of an `entry_points.txt`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct EntryPoint {
+    pub name: String,
+    pub module: String,
+    pub attribute: Option<String>,
+    pub extras: Vec<String>,
+}
+impl EntryPoint {
+    pub fn parse(name: &str, value: &str) -> Result<EntryPoint> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(
+                r"^([\w.]+)\s*(?::\s*([\w.]+))?\s*(?:\[([^\]]*)\])?$"
+            ).unwrap();
+        }
+        let captures = RE.captures(value.trim())
+            .ok_or_else(|| format!("invalid entry point `{} = {}`", name, value))?;
+        Ok(EntryPoint {
+            name: name.trim().to_owned(),
+            module: captures[1].to_owned(),
+            attribute: captures.get(2).map(|x| x.as_str().to_owned()),
+            extras: captures
+                .get(3)
+                .map(|extras| {
+                    extras
+                        .as_str()
+                        .split(',')
+                        .map(|x| x.trim().to_owned())
+                        .filter(|x| x != "")
+                        .collect()
+                })
+                .unwrap_or_else(Vec::new),
+        })
+    }
+
+    /// The script pip generates to run this entry point with `python`. Entry
+    /// points without an attribute to call can't have one.
+    pub fn launcher(&self, python: &Path) -> Result<String> {
+        let attribute = self.attribute
+            .as_ref()
+            .ok_or_else(|| format!("entry point `{}` has nothing to call", self))?;
+        Ok(format!(
+            "{}# -*- coding: utf-8 -*-
+import re
+import sys
+from {} import {}
+if __name__ == '__main__':
+    sys.argv[0] = re.sub(r'(-script\\.pyw|\\.exe)?$', '', sys.argv[0])
+    sys.exit({}())
+",
+            shebang(python),
+            self.module,
+            attribute.split('.').next().unwrap(),
+            attribute
+        ))
+    }
+}
+impl fmt::Display for EntryPoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} = {}", self.name, self.module)?;
+        if let Some(ref attribute) = self.attribute {
+            write!(f, ":{}", attribute)?;
+        }
+        if !self.extras.is_empty() {
+            write!(f, " [{}]", self.extras.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The groups of a distribution's `entry_points.txt`, such as
+/// `console_scripts` and `gui_scripts`.
+#[derive(Debug, PartialEq, Default)]
+pub struct EntryPoints {
+    pub groups: BTreeMap<String, Vec<EntryPoint>>,
+}
+impl EntryPoints {
+    /// Parses the INI style `entry_points.txt` written by setuptools.
+    pub fn parse(text: &str) -> Result<EntryPoints> {
+        let mut groups = BTreeMap::new();
+        let mut group = None;
+        for line in text.lines().map(|x| x.trim()) {
+            if line == "" || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                let name = line[1..line.len() - 1].trim().to_owned();
+                groups.entry(name.clone()).or_insert_with(Vec::new);
+                group = Some(name);
+                continue;
+            }
+            let i = match (line.find('='), group.as_ref()) {
+                (Some(i), Some(_)) => i,
+                _ => bail!("invalid entry_points.txt line `{}`", line),
+            };
+            let entry_point = EntryPoint::parse(&line[..i], &line[i + 1..])?;
+            groups
+                .get_mut(group.as_ref().unwrap())
+                .unwrap()
+                .push(entry_point);
+        }
+        Ok(EntryPoints { groups: groups })
+    }
+
+    pub fn group(&self, name: &str) -> &[EntryPoint] {
+        self.groups.get(name).map_or(&[], |x| x.as_slice())
+    }
+
+    /// The entry points that get a launcher script in the environment's
+    /// `bin` directory.
+    pub fn scripts(&self) -> Vec<&EntryPoint> {
+        self.group("console_scripts")
+            .iter()
+            .chain(self.group("gui_scripts").iter())
+            .collect()
+    }
+}
+
+/// The first line of a script run by `python`. The kernel limits the length
+/// of a shebang and can't handle spaces in it, so like pip this falls back to
+/// a line that is both a shell script and a python string in those cases.
+pub fn shebang(python: &Path) -> String {
+    let python = python.to_string_lossy();
+    if python.len() <= 127 && !python.contains(' ') {
+        format!("#!{}\n", python)
+    } else {
+        format!("#!/bin/sh\n'''exec' \"{}\" \"$0\" \"$@\"\n' '''\n", python)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use entry_points::*;
+
+    #[test]
+    fn parse_entry_points_txt() {
+        let entry_points = EntryPoints::parse(
+            "[console_scripts]
+pytest = pytest:console_main
+py.test = pytest:console_main
+
+[gui_scripts]
+idle = idlelib.pyshell : main.run [gui, tk]
+
+[pytest11]
+",
+        ).unwrap();
+
+        assert_eq!(entry_points.groups.len(), 3);
+        assert_eq!(entry_points.scripts().len(), 3);
+        assert_eq!(
+            entry_points.group("gui_scripts")[0],
+            EntryPoint {
+                name: "idle".to_owned(),
+                module: "idlelib.pyshell".to_owned(),
+                attribute: Some("main.run".to_owned()),
+                extras: vec!["gui".to_owned(), "tk".to_owned()],
+            }
+        );
+        assert_eq!(
+            entry_points.group("gui_scripts")[0].to_string(),
+            "idle = idlelib.pyshell:main.run [gui, tk]"
+        );
+        assert!(entry_points.group("pytest11").is_empty());
+        assert!(EntryPoints::parse("pytest = pytest:main").is_err());
+    }
+
+    #[test]
+    fn launcher_script() {
+        let entry_point = EntryPoint::parse("idle", "idlelib.pyshell:main.run").unwrap();
+
+        let launcher = entry_point.launcher(Path::new("/venv/bin/python")).unwrap();
+
+        assert!(launcher.starts_with("#!/venv/bin/python\n"));
+        assert!(launcher.contains("from idlelib.pyshell import main\n"));
+        assert!(launcher.contains("sys.exit(main.run())"));
+        assert!(EntryPoint::parse("idle", "idlelib").unwrap().launcher(Path::new("/")).is_err());
+        assert!(shebang(Path::new("/my venv/bin/python")).starts_with("#!/bin/sh\n"));
+    }
+}
@@ -22,6 +22,31 @@ error_chain!{
                 description("File not found in archive")
                 display("File not found in archive: `{}`", s)
             }
+            UnsafeArchivePath(name: String) {
+                description("Unsafe path in archive")
+                display("Unsafe path in archive: `{}`", name)
+            }
+            UnsafeArchiveLink(name: String, target: ::std::path::PathBuf) {
+                description("Archive link points outside the archive")
+                display("Archive link points outside the archive: `{}` -> `{}`",
+                        name, target.display())
+            }
+            ArchiveTooManyEntries(limit: usize) {
+                description("Archive has too many entries")
+                display("Archive has more than {} entries", limit)
+            }
+            ArchiveEntryTooLarge(name: String, limit: u64) {
+                description("Archive entry is too large")
+                display("Archive entry is larger than {} bytes: `{}`", limit, name)
+            }
+            ArchiveTooLarge(limit: u64) {
+                description("Archive is too large")
+                display("Archive unpacks to more than {} bytes", limit)
+            }
+            UnsupportedReleaseType(release_type: ::release::ReleaseType) {
+                description("Unsupported release type")
+                display("Unsupported release type: {:?}", release_type)
+            }
             VersionDoesntExist(name: String, v: semver::Version) {
                 description("Version doesn't exist")
                 display("Version doesn't exist: {}: {}", name, v)
@@ -34,5 +59,70 @@ error_chain!{
                 description("Package has no released versions")
                 display("Package has no released versions: {}", s)
             }
+            InvalidVersionSpecifier(s: String) {
+                description("Invalid version specifier")
+                display("Invalid version specifier: `{}`", s)
+            }
+            NoVersionMatchingRequirements(name: String, reqs: String) {
+                description("No version matches requirements")
+                display("No version matches requirements: {}: {}", name, reqs)
+            }
+            MissingDigest(filename: String) {
+                description("Index didn't publish a sha256 digest")
+                display("Index didn't publish a sha256 digest: {}", filename)
+            }
+            HashMismatch(filename: String, expected: String, actual: String) {
+                description("Hash mismatch")
+                display("Hash mismatch: {}: expected {} but got {}", filename, expected, actual)
+            }
+            InvalidWheel(filename: String, reason: String) {
+                description("Invalid wheel")
+                display("Invalid wheel: {}: {}", filename, reason)
+            }
+            InvalidWheelFilename(s: String) {
+                description("Invalid wheel filename")
+                display("Invalid wheel filename: `{}`", s)
+            }
+            InvalidPythonVersion(s: String) {
+                description("Invalid python version")
+                display("Invalid python version: `{}`", s)
+            }
+            InterpreterQueryFailed(s: String) {
+                description("Failed to query python interpreter")
+                display("Failed to query python interpreter: {}", s)
+            }
+            InterpreterNotFound(request: String) {
+                description("No matching python interpreter found")
+                display("No python interpreter matching `{}` found", request)
+            }
+            LockfileOutOfDate(problems: usize) {
+                description("Pipfile.lock is out of date")
+                display("Pipfile.lock is out of date: {} problem(s) found", problems)
+            }
+            IntegrityCheckFailed(distributions: usize) {
+                description("Installed packages don't match their RECORD")
+                display("{} installed package(s) don't match their RECORD", distributions)
+            }
+            InvalidPackageEntry(name: String, reason: String) {
+                description("Invalid Pipfile package entry")
+                display("Invalid Pipfile package entry: {}: {}", name, reason)
+            }
+            UninstallOutsidePrefix(name: String, path: ::std::path::PathBuf) {
+                description("Refusing to uninstall a file outside the environment")
+                display("Refusing to uninstall {}: {} is outside the environment",
+                        name, path.display())
+            }
+            UnsupportedPlatform(os: String, arch: String) {
+                description("Unsupported platform")
+                display("Unsupported platform: {} on {}", os, arch)
+            }
+            UnsupportedIndex(url: String) {
+                description("Unsupported package index")
+                display("Unsupported package index: {}: only PyPI is supported", url)
+            }
+            InvalidMarker(markers: String, reason: String) {
+                description("Invalid environment marker")
+                display("Invalid environment marker: `{}`: {}", markers, reason)
+            }
     }
 }
@@ -0,0 +1,93 @@
+use std::io::{self, Read, Write};
+use sha2::{Digest, Sha256};
+
+use errors::*;
+
+/// Copies `reader` to `writer` a chunk at a time, hashing each chunk as it
+/// passes through, and fails unless the hash is one of `allowed`. There are
+/// no unverified reads: an empty `allowed` always fails. Returns the number
+/// of bytes copied.
+pub fn copy_verified<R: Read, W: Write>(
+    mut reader: R,
+    writer: &mut W,
+    filename: &str,
+    allowed: &[String],
+) -> Result<u64> {
+    let mut hasher = Sha256::default();
+    let mut buf = [0; 64 * 1024];
+    let mut copied = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        hasher.input(&buf[..n]);
+        writer.write_all(&buf[..n])?;
+        copied += n as u64;
+    }
+    let actual = format!("sha256:{:x}", hasher.result());
+    if !allowed.contains(&actual) {
+        bail!(ErrorKind::HashMismatch(
+            filename.to_owned(),
+            allowed.join(", "),
+            actual
+        ));
+    }
+    Ok(copied)
+}
+
+/// Reads `reader` to the end through `copy_verified`.
+pub fn read_verified<R: Read>(reader: R, filename: &str, allowed: &[String]) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    copy_verified(reader, &mut bytes, filename, allowed)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use hashing::*;
+
+    const HELLO_SHA256: &str =
+        "sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn accepts_matching_hash() {
+        let bytes = read_verified(
+            &b"hello"[..],
+            "hello.txt",
+            &["sha256:00".to_owned(), HELLO_SHA256.to_owned()],
+        ).unwrap();
+
+        assert_eq!(bytes, b"hello");
+    }
+
+    #[test]
+    fn rejects_mismatched_hash() {
+        let err = read_verified(&b"hello!"[..], "hello.txt", &[HELLO_SHA256.to_owned()])
+            .unwrap_err();
+
+        match *err.kind() {
+            ErrorKind::HashMismatch(ref filename, _, _) => assert_eq!(filename, "hello.txt"),
+            ref kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn hashes_while_copying() {
+        let bytes = vec![7; 200 * 1024];
+        let allowed = [format!("sha256:{:x}", Sha256::digest(&bytes))];
+        let mut copied = vec![];
+
+        let n = copy_verified(&bytes[..], &mut copied, "big.bin", &allowed).unwrap();
+
+        assert_eq!(n, bytes.len() as u64);
+        assert_eq!(copied, bytes);
+    }
+
+    #[test]
+    fn rejects_when_nothing_to_verify_against() {
+        assert!(read_verified(&b"hello"[..], "hello.txt", &[]).is_err());
+    }
+}
@@ -0,0 +1,601 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use zip::read::ZipArchive;
+
+use archive::{is_zip_symlink, safe_path, ArchiveGuard, ArchiveLimits};
+use entry_points::{shebang, EntryPoints};
+use record::{format_record, parse_record, record_hash_like, RecordEntry};
+use errors::*;
+
+/// The name written to the dist-info `INSTALLER` file.
+const INSTALLER: &str = "pipenv-rs";
+
+/// Where each part of a wheel is installed, like python's sysconfig install
+/// schemes.
+#[derive(Debug, Clone)]
+pub struct InstallScheme {
+    pub purelib: PathBuf,
+    pub platlib: PathBuf,
+    pub scripts: PathBuf,
+    pub headers: PathBuf,
+    pub data: PathBuf,
+}
+impl InstallScheme {
+    /// The posix scheme of an environment rooted at `prefix` such as a
+    /// virtualenv, for python `major.minor` version `python_version`.
+    pub fn for_prefix(prefix: &Path, python_version: &str) -> InstallScheme {
+        let site_packages = prefix
+            .join("lib")
+            .join(format!("python{}", python_version))
+            .join("site-packages");
+        InstallScheme {
+            purelib: site_packages.clone(),
+            platlib: site_packages,
+            scripts: prefix.join("bin"),
+            headers: prefix
+                .join("include")
+                .join("site")
+                .join(format!("python{}", python_version)),
+            data: prefix.to_owned(),
+        }
+    }
+
+    /// The directory a `.data/<key>` directory of a wheel is installed to.
+    fn data_dir(&self, key: &str, distribution: &str) -> Option<PathBuf> {
+        match key {
+            "purelib" => Some(self.purelib.clone()),
+            "platlib" => Some(self.platlib.clone()),
+            "scripts" => Some(self.scripts.clone()),
+            "headers" => Some(self.headers.join(distribution)),
+            "data" => Some(self.data.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// A wheel that has been unpacked by `install_wheel`.
+#[derive(Debug)]
+pub struct InstalledWheel {
+    pub dist_info: PathBuf,
+    pub record: Vec<RecordEntry>,
+}
+
+/// A change `install_wheel` made to the filesystem, undone if it fails.
+enum Change {
+    CreatedDir(PathBuf),
+    CreatedFile(PathBuf),
+    /// A file that existed before, with its previous contents.
+    ReplacedFile(PathBuf, Vec<u8>),
+}
+
+/// Unpacks the wheel `reader`, named `filename`, into `scheme` as described
+/// in PEP 427. Every file is checked against the wheel's own `RECORD` as it's
+/// unpacked, and on failure the files and directories already created are
+/// removed again and the files overwritten are restored.
+/// Scripts are set up to run with the interpreter `python`, and `requested`
+/// marks the distribution as one the user asked for rather than a dependency.
+pub fn install_wheel<R: Read + Seek>(
+    reader: R,
+    filename: &str,
+    scheme: &InstallScheme,
+    python: &Path,
+    requested: bool,
+    limits: &ArchiveLimits,
+) -> Result<InstalledWheel> {
+    let mut changes = vec![];
+    let mut guard = ArchiveGuard::new(limits);
+    let result = unpack_wheel(
+        reader,
+        filename,
+        scheme,
+        python,
+        requested,
+        &mut guard,
+        &mut changes,
+    );
+    if result.is_err() {
+        // Directories were created before the files in them
+        for change in changes.iter().rev() {
+            let _ = match *change {
+                Change::CreatedDir(ref dir) => fs::remove_dir(dir),
+                Change::CreatedFile(ref path) => fs::remove_file(path),
+                Change::ReplacedFile(ref path, ref bytes) => fs::write(path, bytes),
+            };
+        }
+    }
+    result
+}
+
+fn unpack_wheel<R: Read + Seek>(
+    reader: R,
+    filename: &str,
+    scheme: &InstallScheme,
+    python: &Path,
+    requested: bool,
+    guard: &mut ArchiveGuard,
+    changes: &mut Vec<Change>,
+) -> Result<InstalledWheel> {
+    let invalid = |reason: &str| ErrorKind::InvalidWheel(filename.to_owned(), reason.to_owned());
+    let mut archive = ZipArchive::new(reader)?;
+    let mut names = vec![];
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        guard.entry(file.name(), file.size())?;
+        names.push(file.name().to_owned());
+    }
+
+    let dist_info =
+        find_dist_info(&names).ok_or_else(|| invalid("no single .dist-info directory"))?;
+    let distribution = dist_info.trim_right_matches(".dist-info").to_owned();
+    let data_prefix = format!("{}.data/", distribution);
+    let wheel_file = read_archive_text(&mut archive, guard, &format!("{}/WHEEL", dist_info))?;
+    let root = if wheel_metadata(&wheel_file, filename)?.root_is_purelib {
+        scheme.purelib.clone()
+    } else {
+        scheme.platlib.clone()
+    };
+    let record_name = format!("{}/RECORD", dist_info);
+    let expected: HashMap<String, RecordEntry> =
+        parse_record(&read_archive_text(&mut archive, guard, &record_name)?)?
+            .into_iter()
+            .map(|entry| (entry.path.clone(), entry))
+            .collect();
+
+    let mut record = vec![];
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = file.name().to_owned();
+        if name.ends_with('/') || name == record_name {
+            continue;
+        }
+        let (destination, executable) = if name.starts_with(&data_prefix) {
+            let rest = &name[data_prefix.len()..];
+            let (key, path) = match rest.find('/') {
+                Some(i) => (&rest[..i], &rest[i + 1..]),
+                None => bail!(invalid(&format!("unexpected file {}", name))),
+            };
+            let dir = scheme
+                .data_dir(key, &distribution)
+                .ok_or_else(|| invalid(&format!("unknown data directory {}", key)))?;
+            (dir.join(safe_path(path)?), key == "scripts")
+        } else {
+            (root.join(safe_path(&name)?), false)
+        };
+
+        let mut bytes = guard.read(&name, &mut file)?;
+        if is_zip_symlink(&file) {
+            // Installed as a regular file holding the target, as pip does
+            let target = PathBuf::from(String::from_utf8_lossy(&bytes).into_owned());
+            guard.link(&name, &target, false)?;
+        }
+        let is_signature = name == format!("{}.jws", record_name)
+            || name == format!("{}.p7s", record_name);
+        match expected.get(&name) {
+            Some(entry) => check_record_entry(entry, &bytes, filename)?,
+            None if is_signature => (),
+            None => bail!(invalid(&format!("{} isn't listed in RECORD", name))),
+        }
+        if executable {
+            bytes = rewrite_shebang(bytes, python);
+        }
+
+        write_file(&destination, &bytes, executable, changes)?;
+        record.push(RecordEntry::new(&record_path(&destination, &root), &bytes));
+    }
+    if let Some(missing) = expected
+        .values()
+        .find(|entry| entry.hash.is_some() && !names.contains(&entry.path))
+    {
+        bail!(invalid(&format!("{} is listed in RECORD but missing", missing.path)));
+    }
+
+    let entry_points_name = format!("{}/entry_points.txt", dist_info);
+    if names.contains(&entry_points_name) {
+        let entry_points =
+            EntryPoints::parse(&read_archive_text(&mut archive, guard, &entry_points_name)?)?;
+        for entry_point in entry_points.scripts() {
+            let path = scheme.scripts.join(safe_path(&entry_point.name)?);
+            let launcher = entry_point.launcher(python)?;
+            write_file(&path, launcher.as_bytes(), true, changes)?;
+            record.push(RecordEntry::new(&record_path(&path, &root), launcher.as_bytes()));
+        }
+    }
+
+    let dist_info_dir = root.join(&dist_info);
+    let mut generated = vec![("INSTALLER", format!("{}\n", INSTALLER))];
+    if requested {
+        generated.push(("REQUESTED", String::new()));
+    }
+    for &(name, ref contents) in generated.iter() {
+        let path = dist_info_dir.join(name);
+        write_file(&path, contents.as_bytes(), false, changes)?;
+        record.push(RecordEntry::new(&record_path(&path, &root), contents.as_bytes()));
+    }
+    record.push(RecordEntry {
+        path: record_name.clone(),
+        hash: None,
+        size: None,
+    });
+    write_file(
+        &root.join(&record_name),
+        format_record(&record).as_bytes(),
+        false,
+        changes,
+    )?;
+    Ok(InstalledWheel {
+        dist_info: dist_info_dir,
+        record: record,
+    })
+}
+
+/// The `{name}-{version}.dist-info` directory at the top of the archive.
+fn find_dist_info(names: &[String]) -> Option<String> {
+    let mut dist_infos: Vec<&str> = names
+        .iter()
+        .filter_map(|name| name.split('/').next())
+        .filter(|dir| dir.ends_with(".dist-info"))
+        .collect();
+    dist_infos.sort();
+    dist_infos.dedup();
+    if dist_infos.len() == 1 {
+        Some(dist_infos[0].to_owned())
+    } else {
+        None
+    }
+}
+
+fn read_archive_text<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    guard: &mut ArchiveGuard,
+    name: &str,
+) -> Result<String> {
+    let file = archive
+        .by_name(name)
+        .chain_err(|| ErrorKind::ArchiveFileNotFound(name.to_owned()))?;
+    guard.read_to_string(name, file)
+}
+
+struct WheelFileMetadata {
+    root_is_purelib: bool,
+}
+
+/// Reads the dist-info `WHEEL` file, refusing wheels of a newer major
+/// Wheel-Version than PEP 427's 1.0.
+fn wheel_metadata(text: &str, filename: &str) -> Result<WheelFileMetadata> {
+    let mut version = None;
+    let mut root_is_purelib = None;
+    for line in text.lines() {
+        if let Some(i) = line.find(':') {
+            let value = line[i + 1..].trim();
+            match line[..i].trim() {
+                "Wheel-Version" => version = Some(value.to_owned()),
+                "Root-Is-Purelib" => root_is_purelib = Some(value == "true"),
+                _ => (),
+            }
+        }
+    }
+    let invalid = |reason: &str| ErrorKind::InvalidWheel(filename.to_owned(), reason.to_owned());
+    match version {
+        Some(ref version) if version.split('.').next() == Some("1") => (),
+        Some(version) => bail!(invalid(&format!("unsupported Wheel-Version {}", version))),
+        None => bail!(invalid("WHEEL has no Wheel-Version")),
+    }
+    Ok(WheelFileMetadata {
+        root_is_purelib: root_is_purelib.ok_or_else(|| invalid("WHEEL has no Root-Is-Purelib"))?,
+    })
+}
+
+fn check_record_entry(entry: &RecordEntry, bytes: &[u8], filename: &str) -> Result<()> {
+    let file = format!("{} ({})", filename, entry.path);
+    let expected = match entry.hash {
+        Some(ref hash) => hash,
+        None => bail!(ErrorKind::InvalidWheel(file, "no hash in RECORD".to_owned())),
+    };
+    let actual = match record_hash_like(expected, bytes) {
+        Some(actual) => actual,
+        None => bail!(ErrorKind::InvalidWheel(
+            file,
+            format!("unsupported RECORD hash {}", expected)
+        )),
+    };
+    if *expected != actual {
+        bail!(ErrorKind::HashMismatch(file, expected.to_owned(), actual));
+    }
+    if entry.size.map_or(false, |size| size != bytes.len() as u64) {
+        bail!(ErrorKind::InvalidWheel(file, "size doesn't match RECORD".to_owned()));
+    }
+    Ok(())
+}
+
+/// Points a script's `#!python` placeholder shebang, which wheels use since
+/// they don't know where they'll be installed, at `python`. Like pip, any
+/// first line starting with `#!python`, eg. `#!pythonw` or `#!python3`, is
+/// taken to be the placeholder.
+fn rewrite_shebang(bytes: Vec<u8>, python: &Path) -> Vec<u8> {
+    let first_line_end = bytes.iter().position(|&x| x == b'\n').unwrap_or(bytes.len());
+    let first_line = String::from_utf8_lossy(&bytes[..first_line_end]);
+    let first_line = first_line.trim_right_matches('\r');
+    if !first_line.starts_with("#!python") {
+        return bytes;
+    }
+    let arguments = first_line
+        .find(char::is_whitespace)
+        .map_or("", |i| &first_line[i..]);
+    let mut rewritten = shebang(python);
+    if arguments != "" && rewritten.lines().count() == 1 {
+        rewritten = format!("{}{}\n", rewritten.trim_right(), arguments);
+    }
+    let mut rewritten = rewritten.into_bytes();
+    rewritten.extend(&bytes[(first_line_end + 1).min(bytes.len())..]);
+    rewritten
+}
+
+/// `path` relative to `root`, with `/` separators as `RECORD` uses.
+fn record_path(path: &Path, root: &Path) -> String {
+    let path: Vec<Component> = path.components().collect();
+    let root: Vec<Component> = root.components().collect();
+    let common = path.iter()
+        .zip(root.iter())
+        .take_while(|&(a, b)| a == b)
+        .count();
+    let mut parts = vec![".."; root.len() - common];
+    parts.extend(path[common..].iter().map(|x| x.as_os_str().to_str().unwrap_or("")));
+    parts.join("/")
+}
+
+/// Writes `bytes` to `path`, adding what that changed, including any
+/// directories created for it, to `changes`.
+fn write_file(
+    path: &Path,
+    bytes: &[u8],
+    executable: bool,
+    changes: &mut Vec<Change>,
+) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        let missing: Vec<&Path> = parent
+            .ancestors()
+            .take_while(|dir| dir.as_os_str() != "" && !dir.exists())
+            .collect();
+        for dir in missing.into_iter().rev() {
+            fs::create_dir(dir).chain_err(|| format!("failed to create {}", dir.display()))?;
+            changes.push(Change::CreatedDir(dir.to_owned()));
+        }
+    }
+    let change = if path.is_file() {
+        let previous = fs::read(path).chain_err(|| format!("failed to read {}", path.display()))?;
+        Change::ReplacedFile(path.to_owned(), previous)
+    } else {
+        Change::CreatedFile(path.to_owned())
+    };
+    fs::write(path, bytes).chain_err(|| format!("failed to write {}", path.display()))?;
+    changes.push(change);
+    if executable {
+        set_executable(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+    use tempdir::TempDir;
+    use zip::write::{FileOptions, ZipWriter};
+    use install_wheel::*;
+
+    /// A wheel containing `files`, with a RECORD listing every one of them
+    /// unless `record` is given.
+    fn make_wheel(files: &[(&str, &str)], record: Option<&str>) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        let mut entries = vec![];
+        for &(name, contents) in files.iter() {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+            entries.push(RecordEntry::new(name, contents.as_bytes()));
+        }
+        let generated = format_record(&entries);
+        writer.start_file("demo-1.0.dist-info/RECORD", FileOptions::default()).unwrap();
+        writer.write_all(record.unwrap_or(&generated).as_bytes()).unwrap();
+        Cursor::new(writer.finish().unwrap().into_inner())
+    }
+
+    const FILENAME: &str = "demo-1.0-py3-none-any.whl";
+    const WHEEL: (&str, &str) = (
+        "demo-1.0.dist-info/WHEEL",
+        "Wheel-Version: 1.0\nRoot-Is-Purelib: true\nTag: py3-none-any\n",
+    );
+
+    #[test]
+    fn installs_into_scheme() {
+        let prefix = TempDir::new("install_wheel").unwrap();
+        let scheme = InstallScheme::for_prefix(prefix.path(), "3.6");
+        let python = prefix.path().join("bin/python");
+        let limits = ArchiveLimits::default();
+        let wheel = make_wheel(
+            &[
+                ("demo/__init__.py", "VERSION = '1.0'\n"),
+                ("demo-1.0.data/scripts/demo-run", "#!python -u\nimport demo\n"),
+                ("demo-1.0.data/scripts/demo-crlf", "#!python\r\nimport demo\r\n"),
+                ("demo-1.0.data/scripts/demo-3", "#!python3\nimport demo\n"),
+                ("demo-1.0.data/headers/demo.h", "int demo;\n"),
+                WHEEL,
+                (
+                    "demo-1.0.dist-info/entry_points.txt",
+                    "[console_scripts]\ndemo = demo.cli:main\n",
+                ),
+            ],
+            None,
+        );
+
+        let installed = install_wheel(wheel, FILENAME, &scheme, &python, true, &limits).unwrap();
+
+        let site_packages = prefix.path().join("lib/python3.6/site-packages");
+        assert_eq!(installed.dist_info, site_packages.join("demo-1.0.dist-info"));
+        assert!(site_packages.join("demo/__init__.py").exists());
+        assert_eq!(
+            fs::read_to_string(prefix.path().join("bin/demo-run")).unwrap(),
+            format!("#!{} -u\nimport demo\n", python.display())
+        );
+        assert_eq!(
+            fs::read_to_string(prefix.path().join("bin/demo-crlf")).unwrap(),
+            format!("#!{}\nimport demo\r\n", python.display())
+        );
+        assert_eq!(
+            fs::read_to_string(prefix.path().join("bin/demo-3")).unwrap(),
+            format!("#!{}\nimport demo\n", python.display())
+        );
+        let launcher = fs::read_to_string(prefix.path().join("bin/demo")).unwrap();
+        assert!(launcher.starts_with(&format!("#!{}\n", python.display())));
+        assert!(launcher.contains("from demo.cli import main\n"));
+        assert!(prefix.path().join("include/site/python3.6/demo-1.0/demo.h").exists());
+        assert_eq!(
+            fs::read_to_string(installed.dist_info.join("INSTALLER")).unwrap(),
+            "pipenv-rs\n"
+        );
+        assert!(installed.dist_info.join("REQUESTED").exists());
+
+        let record = fs::read_to_string(installed.dist_info.join("RECORD")).unwrap();
+        let paths: Vec<String> = parse_record(&record)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.path)
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "demo/__init__.py",
+                "../../../bin/demo-run",
+                "../../../bin/demo-crlf",
+                "../../../bin/demo-3",
+                "../../../include/site/python3.6/demo-1.0/demo.h",
+                "demo-1.0.dist-info/WHEEL",
+                "demo-1.0.dist-info/entry_points.txt",
+                "../../../bin/demo",
+                "demo-1.0.dist-info/INSTALLER",
+                "demo-1.0.dist-info/REQUESTED",
+                "demo-1.0.dist-info/RECORD",
+            ]
+        );
+        assert_eq!(parse_record(&record).unwrap(), installed.record);
+    }
+
+    #[test]
+    fn rejects_tampered_file_and_cleans_up() {
+        let prefix = TempDir::new("install_wheel").unwrap();
+        let scheme = InstallScheme::for_prefix(prefix.path(), "3.6");
+        let python = prefix.path().join("bin/python");
+        let limits = ArchiveLimits::default();
+        let record = format_record(&[
+            RecordEntry::new("demo/__init__.py", b""),
+            RecordEntry::new("demo/core.py", b"original"),
+            RecordEntry::new(WHEEL.0, WHEEL.1.as_bytes()),
+        ]);
+        let wheel = make_wheel(
+            &[("demo/__init__.py", ""), ("demo/core.py", "tampered"), WHEEL],
+            Some(&record),
+        );
+
+        let err = install_wheel(wheel, FILENAME, &scheme, &python, false, &limits).unwrap_err();
+
+        match *err.kind() {
+            ErrorKind::HashMismatch(ref file, _, _) => assert!(file.contains("demo/core.py")),
+            ref kind => panic!("unexpected error {:?}", kind),
+        }
+        assert!(!scheme.purelib.join("demo").exists());
+        assert!(!prefix.path().join("lib").exists());
+    }
+
+    #[test]
+    fn restores_overwritten_files_on_failure() {
+        let prefix = TempDir::new("install_wheel").unwrap();
+        let scheme = InstallScheme::for_prefix(prefix.path(), "3.6");
+        let python = prefix.path().join("bin/python");
+        let limits = ArchiveLimits::default();
+        let existing = scheme.purelib.join("demo/__init__.py");
+        fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        fs::write(&existing, "VERSION = '0.9'\n").unwrap();
+        let record = format_record(&[
+            RecordEntry::new("demo/__init__.py", b""),
+            RecordEntry::new("demo/core.py", b"original"),
+            RecordEntry::new(WHEEL.0, WHEEL.1.as_bytes()),
+        ]);
+        let wheel = make_wheel(
+            &[("demo/__init__.py", ""), ("demo/core.py", "tampered"), WHEEL],
+            Some(&record),
+        );
+
+        install_wheel(wheel, FILENAME, &scheme, &python, false, &limits).unwrap_err();
+
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "VERSION = '0.9'\n");
+        assert!(!scheme.purelib.join("demo/core.py").exists());
+    }
+
+    #[test]
+    fn accepts_sha384_and_sha512_record_hashes() {
+        let prefix = TempDir::new("install_wheel").unwrap();
+        let scheme = InstallScheme::for_prefix(prefix.path(), "3.6");
+        let python = prefix.path().join("bin/python");
+        let limits = ArchiveLimits::default();
+        let entry = |path: &str, algorithm: &str, contents: &str| {
+            let like = format!("{}=", algorithm);
+            RecordEntry {
+                path: path.to_owned(),
+                hash: record_hash_like(&like, contents.as_bytes()),
+                size: Some(contents.len() as u64),
+            }
+        };
+        let record = format_record(&[
+            entry("demo/__init__.py", "sha384", "x = 1\n"),
+            entry(WHEEL.0, "sha512", WHEEL.1),
+        ]);
+        let wheel = make_wheel(&[("demo/__init__.py", "x = 1\n"), WHEEL], Some(&record));
+        install_wheel(wheel, FILENAME, &scheme, &python, false, &limits).unwrap();
+        assert!(scheme.purelib.join("demo/__init__.py").exists());
+
+        let prefix = TempDir::new("install_wheel").unwrap();
+        let scheme = InstallScheme::for_prefix(prefix.path(), "3.6");
+        let record = format_record(&[
+            entry("demo/__init__.py", "sha512", "x = 2\n"),
+            entry(WHEEL.0, "sha512", WHEEL.1),
+        ]);
+        let wheel = make_wheel(&[("demo/__init__.py", "x = 1\n"), WHEEL], Some(&record));
+        let err = install_wheel(wheel, FILENAME, &scheme, &python, false, &limits).unwrap_err();
+        match *err.kind() {
+            ErrorKind::HashMismatch(_, ref expected, _) => assert!(expected.starts_with("sha512=")),
+            ref kind => panic!("unexpected error {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn rejects_unrecorded_files_and_unsafe_paths() {
+        let prefix = TempDir::new("install_wheel").unwrap();
+        let scheme = InstallScheme::for_prefix(prefix.path(), "3.6");
+        let python = prefix.path().join("bin/python");
+        let limits = ArchiveLimits::default();
+        let record = format_record(&[RecordEntry::new(WHEEL.0, WHEEL.1.as_bytes())]);
+
+        let unrecorded = make_wheel(&[("demo/extra.py", ""), WHEEL], Some(&record));
+        assert!(install_wheel(unrecorded, FILENAME, &scheme, &python, false, &limits).is_err());
+
+        let escaping = make_wheel(&[("../escape.py", ""), WHEEL], None);
+        let err = install_wheel(escaping, FILENAME, &scheme, &python, false, &limits).unwrap_err();
+        match *err.kind() {
+            ErrorKind::UnsafeArchivePath(ref name) => assert_eq!(name, "../escape.py"),
+            ref kind => panic!("unexpected error {:?}", kind),
+        }
+        assert!(!prefix.path().join("lib/python3.6/escape.py").exists());
+    }
+}
@@ -0,0 +1,299 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use serde_json;
+
+use pypi::canonical_name;
+use record::{parse_record, RecordEntry};
+use errors::*;
+
+/// How a distribution's metadata was installed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetadataKind {
+    /// A `.dist-info` directory, as written by installing a wheel.
+    DistInfo,
+    /// A `.egg-info` directory or file, as left by `setup.py install` or
+    /// `setup.py develop`.
+    EggInfo,
+}
+
+/// PEP 610's `direct_url.json`, recording that a distribution was installed
+/// from a URL, a VCS checkout or a local directory rather than an index.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct DirectUrl {
+    pub url: String,
+    #[serde(default)]
+    pub vcs_info: Option<VcsInfo>,
+    #[serde(default)]
+    pub dir_info: Option<DirInfo>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct VcsInfo {
+    pub vcs: String,
+    pub commit_id: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct DirInfo {
+    #[serde(default)]
+    pub editable: bool,
+}
+
+/// A distribution found in a site-packages directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstalledDistribution {
+    /// The name as given in the distribution's metadata.
+    pub name: String,
+    pub version: String,
+    /// The `.dist-info` or `.egg-info` holding the metadata.
+    pub path: PathBuf,
+    pub kind: MetadataKind,
+    /// Whatever tool the `INSTALLER` file names, eg. `pip`.
+    pub installer: Option<String>,
+    /// Whether the user asked for this distribution rather than it being
+    /// installed as a dependency, from PEP 376's `REQUESTED` file.
+    pub requested: bool,
+    pub direct_url: Option<DirectUrl>,
+}
+impl InstalledDistribution {
+    /// Reads the distribution whose metadata is at `path`, or `None` when
+    /// `path` doesn't hold any, eg. a directory left behind by a failed
+    /// install.
+    pub fn read(path: &Path) -> Result<Option<InstalledDistribution>> {
+        let file_name = path.file_name().map_or("".into(), |x| x.to_string_lossy());
+        let (kind, metadata_path) = if file_name.ends_with(".dist-info") {
+            (MetadataKind::DistInfo, path.join("METADATA"))
+        } else if file_name.ends_with(".egg-info") && path.is_dir() {
+            (MetadataKind::EggInfo, path.join("PKG-INFO"))
+        } else if file_name.ends_with(".egg-info") {
+            (MetadataKind::EggInfo, path.to_owned())
+        } else {
+            return Ok(None);
+        };
+        if !metadata_path.is_file() {
+            return Ok(None);
+        }
+        let metadata = fs::read_to_string(&metadata_path)?;
+        let field = |name: &str| {
+            metadata_field(&metadata, name).ok_or_else(|| {
+                format!("{} has no {}", metadata_path.display(), name)
+            })
+        };
+        let name = field("Name")?;
+        let version = field("Version")?;
+
+        let read_optional = |name: &str| -> Result<Option<String>> {
+            let file = path.join(name);
+            if path.is_dir() && file.is_file() {
+                Ok(Some(fs::read_to_string(file)?))
+            } else {
+                Ok(None)
+            }
+        };
+        let direct_url = match read_optional("direct_url.json")? {
+            Some(json) => Some(serde_json::from_str(&json).chain_err(|| {
+                format!("invalid direct_url.json in {}", path.display())
+            })?),
+            None => None,
+        };
+        Ok(Some(InstalledDistribution {
+            name: name,
+            version: version,
+            path: path.to_owned(),
+            kind: kind,
+            installer: read_optional("INSTALLER")?
+                .map(|x| x.trim().to_owned())
+                .filter(|x| x != ""),
+            requested: path.join("REQUESTED").is_file(),
+            direct_url: direct_url,
+        }))
+    }
+
+    /// The directory the paths in this distribution's RECORD are relative
+    /// to, ie. the site-packages directory holding its metadata.
+    pub fn root(&self) -> &Path {
+        self.path.parent().unwrap_or_else(|| Path::new(""))
+    }
+
+    /// The entries of this distribution's RECORD. Only distributions
+    /// installed from a wheel have one.
+    pub fn record(&self) -> Result<Vec<RecordEntry>> {
+        if self.kind != MetadataKind::DistInfo {
+            bail!(
+                "{} {} wasn't installed from a wheel and has no RECORD",
+                self.name,
+                self.version
+            );
+        }
+        let record_path = self.path.join("RECORD");
+        let text = fs::read_to_string(&record_path)
+            .chain_err(|| format!("failed to read {}", record_path.display()))?;
+        parse_record(&text)
+    }
+
+    /// This distribution as a `pip freeze` style requirement, pointing at
+    /// wherever it was installed from when that wasn't an index.
+    pub fn requirement(&self) -> String {
+        match self.direct_url {
+            Some(DirectUrl { vcs_info: Some(ref vcs_info), ref url, .. }) => format!(
+                "{} @ {}+{}@{}",
+                self.name, vcs_info.vcs, url, vcs_info.commit_id
+            ),
+            Some(DirectUrl { dir_info: Some(DirInfo { editable: true }), ref url, .. }) => {
+                format!("-e {}", url)
+            }
+            Some(ref direct_url) => format!("{} @ {}", self.name, direct_url.url),
+            None => format!("{}=={}", self.name, self.version),
+        }
+    }
+}
+
+/// The RECORD entry `path` relative to `root`, resolving any `..` without
+/// touching the filesystem.
+pub fn resolve_record_path(root: &Path, path: &str) -> PathBuf {
+    let mut resolved = root.to_owned();
+    for component in Path::new(path).components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => (),
+            component => resolved.push(component.as_os_str()),
+        }
+    }
+    resolved
+}
+
+/// The value of the first `field` header of RFC 822 style metadata such as
+/// METADATA or PKG-INFO.
+fn metadata_field(metadata: &str, field: &str) -> Option<String> {
+    metadata
+        .lines()
+        .take_while(|line| line.trim() != "")
+        .filter_map(|line| {
+            let i = line.find(':')?;
+            if line[..i].trim().eq_ignore_ascii_case(field) {
+                Some(line[i + 1..].trim().to_owned())
+            } else {
+                None
+            }
+        })
+        .next()
+}
+
+/// Every distribution installed in `site_packages`, sorted by canonical
+/// name. Like pip, distributions whose metadata can't be read are skipped
+/// with a warning.
+pub fn installed_distributions(site_packages: &Path) -> Result<Vec<InstalledDistribution>> {
+    let mut distributions = vec![];
+    if !site_packages.exists() {
+        return Ok(distributions);
+    }
+    for entry in fs::read_dir(site_packages)? {
+        let path = entry?.path();
+        match InstalledDistribution::read(&path) {
+            Ok(Some(distribution)) => distributions.push(distribution),
+            Ok(None) => (),
+            Err(e) => eprintln!("warning: skipping {}: {}", path.display(), e),
+        }
+    }
+    distributions.sort_by_key(|x| canonical_name(&x.name));
+    Ok(distributions)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use test_support::write;
+    use installed::*;
+
+    #[test]
+    fn inventory_site_packages() {
+        let dir = TempDir::new("site-packages").unwrap();
+        let site_packages = dir.path();
+        write(
+            site_packages,
+            "requests-2.18.4.dist-info/METADATA",
+            "Metadata-Version: 2.0\nName: requests\nVersion: 2.18.4\n\nVersion: 0\n",
+        );
+        write(site_packages, "requests-2.18.4.dist-info/INSTALLER", "pip\n");
+        write(site_packages, "requests-2.18.4.dist-info/REQUESTED", "");
+        write(
+            site_packages,
+            "Django-1.11.dist-info/METADATA",
+            "Metadata-Version: 2.1\nname: Django\nversion: 1.11\n",
+        );
+        write(
+            site_packages,
+            "Django-1.11.dist-info/direct_url.json",
+            r#"{"url": "https://github.com/django/django", "vcs_info": {"vcs": "git",
+                "commit_id": "7f1f7e0", "requested_revision": "stable/1.11.x"}}"#,
+        );
+        write(
+            site_packages,
+            "six-1.11.0-py3.6.egg-info",
+            "Metadata-Version: 1.1\nName: six\nVersion: 1.11.0\n",
+        );
+        write(
+            site_packages,
+            "mypkg.egg-info/PKG-INFO",
+            "Metadata-Version: 1.1\nName: mypkg\nVersion: 0.1\n",
+        );
+        fs::create_dir(site_packages.join("broken-1.0.dist-info")).unwrap();
+        write(site_packages, "noversion-1.0.dist-info/METADATA", "Name: noversion\n");
+        write(site_packages, "badurl-1.0.dist-info/METADATA", "Name: badurl\nVersion: 1.0\n");
+        write(site_packages, "badurl-1.0.dist-info/direct_url.json", "{");
+        fs::create_dir(site_packages.join("latin1-1.0.dist-info")).unwrap();
+        fs::write(
+            site_packages.join("latin1-1.0.dist-info/METADATA"),
+            b"Name: latin1\nVersion: 1.0\nAuthor: Fran\xe7ois\n",
+        ).unwrap();
+        fs::create_dir(site_packages.join("requests")).unwrap();
+
+        let distributions = installed_distributions(site_packages).unwrap();
+
+        let names: Vec<&str> = distributions.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, vec!["Django", "mypkg", "requests", "six"]);
+        let requests = &distributions[2];
+        assert_eq!(requests.version, "2.18.4");
+        assert_eq!(requests.kind, MetadataKind::DistInfo);
+        assert_eq!(requests.installer, Some("pip".to_owned()));
+        assert!(requests.requested);
+        assert_eq!(requests.requirement(), "requests==2.18.4");
+        assert_eq!(distributions[1].kind, MetadataKind::EggInfo);
+        assert!(!distributions[1].requested);
+        assert_eq!(distributions[3].version, "1.11.0");
+        assert_eq!(
+            distributions[0].requirement(),
+            "Django @ git+https://github.com/django/django@7f1f7e0"
+        );
+    }
+
+    #[test]
+    fn freeze_direct_urls() {
+        let mut distribution = InstalledDistribution {
+            name: "mypkg".to_owned(),
+            version: "0.1".to_owned(),
+            path: PathBuf::from("mypkg-0.1.dist-info"),
+            kind: MetadataKind::DistInfo,
+            installer: None,
+            requested: true,
+            direct_url: Some(DirectUrl {
+                url: "file:///src/mypkg".to_owned(),
+                vcs_info: None,
+                dir_info: Some(DirInfo { editable: true }),
+            }),
+        };
+        assert_eq!(distribution.requirement(), "-e file:///src/mypkg");
+
+        distribution.direct_url = Some(DirectUrl {
+            url: "https://example.com/mypkg-0.1.tar.gz".to_owned(),
+            vcs_info: None,
+            dir_info: None,
+        });
+        assert_eq!(
+            distribution.requirement(),
+            "mypkg @ https://example.com/mypkg-0.1.tar.gz"
+        );
+    }
+}
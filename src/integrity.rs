@@ -0,0 +1,221 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use installed::{resolve_record_path, InstalledDistribution, MetadataKind};
+use record::record_hash_like;
+use errors::*;
+
+/// How an installed distribution differs from its RECORD. Paths are as
+/// written in RECORD, or relative to site-packages for unexpected files.
+#[derive(Debug, Default, PartialEq)]
+pub struct IntegrityReport {
+    /// Files whose hash or size no longer matches RECORD.
+    pub modified: Vec<String>,
+    pub missing: Vec<String>,
+    /// Files in the distribution's directories that no RECORD lists.
+    pub unexpected: Vec<String>,
+    /// Whether RECORD itself couldn't be read, so nothing else was checked.
+    pub unreadable_record: bool,
+}
+impl IntegrityReport {
+    pub fn is_clean(&self) -> bool {
+        self.modified.is_empty() && self.missing.is_empty() && self.unexpected.is_empty()
+            && !self.unreadable_record
+    }
+}
+
+/// Checks every distribution of `distributions` that was installed from a
+/// wheel against its RECORD. Namespace packages share directories, so a file
+/// no RECORD lists is reported once, for the distribution with recorded files
+/// in the nearest directory above it. A distribution whose RECORD can't be
+/// read is reported as such, with a warning.
+pub fn check_integrity(
+    distributions: &[InstalledDistribution],
+) -> Result<Vec<(&InstalledDistribution, IntegrityReport)>> {
+    let distributions: Vec<&InstalledDistribution> = distributions
+        .iter()
+        .filter(|x| x.kind == MetadataKind::DistInfo)
+        .collect();
+    let mut records = vec![];
+    let mut recorded = HashSet::new();
+    // Directories holding recorded files, and the top level directories of
+    // site-packages they're in, with the first distribution found there
+    let mut dir_owners = HashMap::new();
+    let mut top_level_dirs = BTreeMap::new();
+    for (i, distribution) in distributions.iter().enumerate() {
+        let record = match distribution.record() {
+            Ok(record) => Some(record),
+            Err(e) => {
+                eprintln!(
+                    "warning: can't check {} {}: {}",
+                    distribution.name, distribution.version, e
+                );
+                None
+            }
+        };
+        for entry in record.iter().flatten() {
+            let path = resolve_record_path(distribution.root(), &entry.path);
+            let mut components = Path::new(&entry.path).components();
+            if let (Some(Component::Normal(dir)), Some(_)) =
+                (components.next(), components.next())
+            {
+                top_level_dirs.entry(distribution.root().join(dir)).or_insert(i);
+                if let Some(parent) = path.parent() {
+                    dir_owners.entry(parent.to_owned()).or_insert(i);
+                }
+            }
+            recorded.insert(path);
+        }
+        records.push(record);
+    }
+
+    let mut reports = vec![];
+    for (distribution, record) in distributions.iter().zip(records) {
+        let mut report = IntegrityReport::default();
+        let record = match record {
+            Some(record) => record,
+            None => {
+                report.unreadable_record = true;
+                reports.push(report);
+                continue;
+            }
+        };
+        for entry in record.iter() {
+            let path = resolve_record_path(distribution.root(), &entry.path);
+            let bytes = match fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+                    report.missing.push(entry.path.to_owned());
+                    continue;
+                }
+                Err(e) => {
+                    return Err(e).chain_err(|| format!("failed to read {}", path.display()));
+                }
+            };
+            // Hashes with algorithms PEP 376 doesn't allow can't be checked
+            let hash_differs = entry.hash.as_ref().map_or(false, |hash| {
+                record_hash_like(hash, &bytes).map_or(false, |actual| *hash != actual)
+            });
+            let size_differs = entry.size.map_or(false, |size| size != bytes.len() as u64);
+            if hash_differs || size_differs {
+                report.modified.push(entry.path.to_owned());
+            }
+        }
+        reports.push(report);
+    }
+
+    for (dir, &top_level_owner) in top_level_dirs.iter() {
+        for path in files_in(dir)? {
+            if recorded.contains(&path) {
+                continue;
+            }
+            let owner = path.ancestors()
+                .skip(1)
+                .take_while(|x| x.starts_with(dir))
+                .filter_map(|x| dir_owners.get(x))
+                .next()
+                .cloned()
+                .unwrap_or(top_level_owner);
+            let root = distributions[owner].root();
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            reports[owner]
+                .unexpected
+                .push(relative.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    Ok(distributions.into_iter().zip(reports).collect())
+}
+
+/// Every file under `dir`, leaving out the bytecode python writes as it
+/// imports modules. Symbolic links aren't followed.
+fn files_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let metadata = match fs::symlink_metadata(dir) {
+        Ok(metadata) => metadata,
+        Err(_) => return Ok(files),
+    };
+    if !metadata.is_dir() {
+        return Ok(files);
+    }
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|x| x.path()))
+        .collect::<io::Result<_>>()?;
+    entries.sort();
+    for path in entries {
+        if fs::symlink_metadata(&path)?.is_dir() {
+            if path.file_name().map_or(false, |x| x != "__pycache__") {
+                files.extend(files_in(&path)?);
+            }
+        } else if path.extension().map_or(true, |x| x != "pyc") {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use installed::installed_distributions;
+    use test_support::{install_distribution, write};
+    use integrity::*;
+
+    #[test]
+    fn reports_changes_to_recorded_files() {
+        let dir = TempDir::new("site-packages").unwrap();
+        let site_packages = dir.path();
+        install_distribution(
+            site_packages,
+            "demo",
+            "1.0",
+            &[("demo/__init__.py", "x = 1\n"), ("demo/util.py", ""), ("demo/data.txt", "")],
+        );
+        install_distribution(
+            site_packages,
+            "other",
+            "1.0",
+            &[("other.py", ""), ("demo/plugins/other.py", "")],
+        );
+        write(site_packages, "demo/__init__.py", "x = 2  # debugging\n");
+        fs::remove_file(site_packages.join("demo/data.txt")).unwrap();
+        write(site_packages, "demo/debug.py", "");
+        write(site_packages, "demo/__pycache__/util.cpython-36.pyc", "");
+        write(site_packages, "demo/plugins/new.py", "");
+        write(site_packages, "stray.py", "");
+
+        let distributions = installed_distributions(site_packages).unwrap();
+        let reports = check_integrity(&distributions).unwrap();
+
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].0.name, "demo");
+        assert_eq!(
+            reports[0].1,
+            IntegrityReport {
+                modified: vec!["demo/__init__.py".to_owned()],
+                missing: vec!["demo/data.txt".to_owned()],
+                unexpected: vec!["demo/debug.py".to_owned()],
+                unreadable_record: false,
+            }
+        );
+        assert_eq!(reports[1].1.unexpected, vec!["demo/plugins/new.py"]);
+        assert!(reports[1].1.modified.is_empty() && reports[1].1.missing.is_empty());
+    }
+
+    #[test]
+    fn reports_unreadable_records() {
+        let dir = TempDir::new("site-packages").unwrap();
+        let site_packages = dir.path();
+        install_distribution(site_packages, "demo", "1.0", &[("demo.py", "")]);
+        install_distribution(site_packages, "other", "1.0", &[("other.py", "")]);
+        fs::remove_file(site_packages.join("demo-1.0.dist-info/RECORD")).unwrap();
+
+        let distributions = installed_distributions(site_packages).unwrap();
+        let reports = check_integrity(&distributions).unwrap();
+
+        assert_eq!(reports.len(), 2);
+        assert!(reports[0].1.unreadable_record);
+        assert!(reports[1].1.is_clean());
+    }
+}
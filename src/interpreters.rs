@@ -0,0 +1,252 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use regex::Regex;
+use serde_json;
+
+use errors::*;
+
+/// What `query_interpreter` learns about a python interpreter.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct InterpreterInfo {
+    /// The real interpreter binary, following symlinks and looking through
+    /// any virtualenv the interpreter belongs to.
+    pub executable: PathBuf,
+    /// The full version, eg. `3.6.3`.
+    pub version: String,
+    /// `sys.implementation.name`, eg. `cpython` or `pypy`.
+    pub implementation: String,
+    pub markers: MarkerEnvironment,
+}
+
+/// The PEP 508 environment markers of an interpreter.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct MarkerEnvironment {
+    pub implementation_name: String,
+    pub implementation_version: String,
+    pub os_name: String,
+    pub platform_machine: String,
+    pub platform_python_implementation: String,
+    pub platform_release: String,
+    pub platform_system: String,
+    pub platform_version: String,
+    pub python_full_version: String,
+    pub python_version: String,
+    pub sys_platform: String,
+}
+
+/// `major.minor` of a full version, as used in paths such as `lib/python3.6`.
+pub fn short_version(version: &str) -> String {
+    version.split('.').take(2).collect::<Vec<_>>().join(".")
+}
+
+const QUERY_SCRIPT: &str = "import json, os, platform, sys
+def format_version(info):
+    version = '{0.major}.{0.minor}.{0.micro}'.format(info)
+    if info.releaselevel != 'final':
+        version += info.releaselevel[0] + str(info.serial)
+    return version
+implementation = sys.implementation
+print(json.dumps({
+    'executable': os.path.realpath(getattr(sys, '_base_executable', sys.executable)),
+    'version': '.'.join(map(str, sys.version_info[:3])),
+    'implementation': implementation.name,
+    'markers': {
+        'implementation_name': implementation.name,
+        'implementation_version': format_version(implementation.version),
+        'os_name': os.name,
+        'platform_machine': platform.machine(),
+        'platform_python_implementation': platform.python_implementation(),
+        'platform_release': platform.release(),
+        'platform_system': platform.system(),
+        'platform_version': platform.version(),
+        'python_full_version': platform.python_version(),
+        'python_version': '.'.join(platform.python_version_tuple()[:2]),
+        'sys_platform': sys.platform,
+    },
+}))";
+
+pub fn query_interpreter<P: AsRef<Path>>(executable: P) -> Result<InterpreterInfo> {
+    let executable = executable.as_ref();
+    let output = Command::new(executable)
+        .args(&["-c", QUERY_SCRIPT])
+        .output()
+        .chain_err(|| ErrorKind::InterpreterQueryFailed(executable.display().to_string()))?;
+    if !output.status.success() {
+        bail!(ErrorKind::InterpreterQueryFailed(
+            executable.display().to_string()
+        ));
+    }
+    Ok(serde_json::from_slice(&output.stdout)
+        .chain_err(|| ErrorKind::InterpreterQueryFailed(executable.display().to_string()))?)
+}
+
+/// Asks a python interpreter for its full version, eg. `3.6.3`.
+pub fn python_full_version<P: AsRef<Path>>(executable: P) -> Result<String> {
+    Ok(query_interpreter(executable)?.version)
+}
+
+/// Directories that commonly hold interpreters but often aren't on `PATH`.
+const COMMON_PREFIXES: &[&str] = &[
+    "/usr/local/bin",
+    "/usr/bin",
+    "/opt/homebrew/bin",
+    "/opt/local/bin",
+];
+
+/// Every `python`, `python3` or `python3.X` executable on `PATH`, in pyenv
+/// and asdf install directories, and in common prefixes, without duplicates.
+pub fn find_interpreter_paths() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = env::var_os("PATH")
+        .map(|path| env::split_paths(&path).collect())
+        .unwrap_or_else(Vec::new);
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let pyenv_root = env::var_os("PYENV_ROOT")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|x| x.join(".pyenv")));
+    let asdf_root = env::var_os("ASDF_DATA_DIR")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|x| x.join(".asdf")));
+    let install_dirs = [
+        pyenv_root.map(|x| x.join("versions")),
+        asdf_root.map(|x| x.join("installs").join("python")),
+    ];
+    for install_dir in install_dirs.iter().filter_map(|x| x.as_ref()) {
+        if let Ok(entries) = fs::read_dir(install_dir) {
+            let mut versions: Vec<PathBuf> =
+                entries.filter_map(|x| x.ok()).map(|x| x.path()).collect();
+            versions.sort();
+            dirs.extend(versions.into_iter().map(|x| x.join("bin")));
+        }
+    }
+    dirs.extend(COMMON_PREFIXES.iter().map(PathBuf::from));
+
+    let mut seen = HashSet::new();
+    let mut paths = vec![];
+    for dir in dirs.iter() {
+        for path in interpreters_in_dir(dir) {
+            // The same binary is usually reachable under several names
+            if seen.insert(fs::canonicalize(&path).unwrap_or_else(|_| path.clone())) {
+                paths.push(path);
+            }
+        }
+    }
+    paths
+}
+
+fn interpreters_in_dir(dir: &Path) -> Vec<PathBuf> {
+    lazy_static! {
+        static ref NAME_RE: Regex = Regex::new(r"^python(\d+(\.\d+)?)?$").unwrap();
+    }
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|x| x.ok())
+            .map(|x| x.path())
+            .filter(|x| {
+                x.file_name()
+                    .and_then(|x| x.to_str())
+                    .map_or(false, |x| NAME_RE.is_match(x))
+            })
+            .filter(|x| x.is_file())
+            .collect(),
+        Err(_) => vec![],
+    };
+    paths.sort();
+    paths
+}
+
+/// Every interpreter `find_interpreter_paths` finds that answers a query,
+/// newest version first.
+pub fn discover_interpreters() -> Vec<InterpreterInfo> {
+    let mut seen = HashSet::new();
+    let mut interpreters: Vec<InterpreterInfo> = find_interpreter_paths()
+        .into_iter()
+        .filter_map(|path| query_interpreter(path).ok())
+        .filter(|interpreter| seen.insert(interpreter.executable.clone()))
+        .collect();
+    interpreters.sort_by_key(|x| ::std::cmp::Reverse(version_key(&x.version)));
+    interpreters
+}
+
+fn version_key(version: &str) -> Vec<u32> {
+    version.split('.').map(|x| x.parse().unwrap_or(0)).collect()
+}
+
+/// Whether `version` is what `request` asks for: `3`, `3.6` and `3.6.3` all
+/// match `3.6.3`.
+pub fn version_matches(version: &str, request: &str) -> bool {
+    let version = version_key(version);
+    let request = version_key(request);
+    request.len() <= version.len() && version[..request.len()] == request[..]
+}
+
+/// The newest of `interpreters` matching `request`.
+pub fn best_interpreter(
+    interpreters: Vec<InterpreterInfo>,
+    request: &str,
+) -> Option<InterpreterInfo> {
+    interpreters
+        .into_iter()
+        .filter(|x| version_matches(&x.version, request))
+        .max_by_key(|x| version_key(&x.version))
+}
+
+/// The interpreter `--python` asks for: either a path to one, or a version
+/// to look for among the discovered interpreters.
+pub fn find_python(request: &str) -> Result<InterpreterInfo> {
+    if request.contains('/') {
+        return query_interpreter(request);
+    }
+    if !request.split('.').all(|x| x.parse::<u32>().is_ok()) {
+        bail!(ErrorKind::InvalidPythonVersion(request.to_owned()));
+    }
+    best_interpreter(discover_interpreters(), request)
+        .ok_or_else(|| ErrorKind::InterpreterNotFound(request.to_owned()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use interpreters::*;
+
+    fn interpreter(version: &str) -> InterpreterInfo {
+        InterpreterInfo {
+            executable: PathBuf::from(format!("/usr/bin/python{}", version)),
+            version: version.to_owned(),
+            implementation: "cpython".to_owned(),
+            markers: MarkerEnvironment::default(),
+        }
+    }
+
+    #[test]
+    fn match_requested_version() {
+        assert!(version_matches("3.6.3", "3"));
+        assert!(version_matches("3.6.3", "3.6"));
+        assert!(version_matches("3.6.3", "3.6.3"));
+        assert!(!version_matches("3.6.3", "3.6.4"));
+        assert!(!version_matches("3.10.1", "3.1"));
+
+        let interpreters = vec![interpreter("3.6.3"), interpreter("3.11.7"), interpreter("2.7.18")];
+        assert_eq!(best_interpreter(interpreters.clone(), "3").unwrap().version, "3.11.7");
+        assert_eq!(best_interpreter(interpreters.clone(), "3.6").unwrap().version, "3.6.3");
+        assert_eq!(best_interpreter(interpreters, "3.7"), None);
+    }
+
+    #[test]
+    fn finds_python_executables_by_name() {
+        let dir = TempDir::new("interpreters").unwrap();
+        for name in &["python", "python3", "python3.11", "python3.11-config", "pythonw"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        fs::create_dir(dir.path().join("python2")).unwrap();
+
+        let names: Vec<String> = interpreters_in_dir(dir.path())
+            .iter()
+            .map(|x| x.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+
+        assert_eq!(names, vec!["python", "python3", "python3.11"]);
+    }
+}
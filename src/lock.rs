@@ -0,0 +1,152 @@
+use std::collections::{BTreeMap, HashMap};
+use reqwest;
+
+use archive::ArchiveLimits;
+use pipfile::{lockfile_category, LockedPackage, Lockfile, LockfileMeta, LockfileMetaHash,
+              PackageMap, PackageSource, Pipfile, Source, PIPFILE_SPEC};
+use pypi::canonical_name;
+use resolver::Resolver;
+use tags::TargetPython;
+use version_req::PackageVersionReq;
+use errors::*;
+
+/// The source named `index`, or the first source when `None`, if it's PyPI.
+fn index_source<'a>(sources: &'a [Source], index: Option<&str>) -> Result<&'a Source> {
+    let source = match index {
+        Some(index) => sources
+            .iter()
+            .find(|source| source.name.as_ref().map(|x| x.as_str()) == Some(index))
+            .ok_or_else(|| format!("no [[source]] is named {}", index))?,
+        None => &sources[0],
+    };
+    if !source.is_pypi() {
+        bail!(ErrorKind::UnsupportedIndex(source.url.to_owned()));
+    }
+    Ok(source)
+}
+
+fn lock_section(
+    client: &reqwest::Client,
+    target: &TargetPython,
+    sources: &[Source],
+    packages: &PackageMap,
+    allow_prereleases: bool,
+    limits: &ArchiveLimits,
+) -> Result<BTreeMap<String, LockedPackage>> {
+    let mut locked = BTreeMap::new();
+    let mut reqs = vec![];
+    let mut indexes = HashMap::new();
+    for (name, package_info) in packages.iter() {
+        let unresolved = |locked_package: LockedPackage| LockedPackage {
+            extras: package_info.extras.clone(),
+            markers: package_info.markers.clone(),
+            ..locked_package
+        };
+        let locked_package = match package_info.source {
+            PackageSource::Index {
+                ref version,
+                ref index,
+            } => {
+                let source = index_source(sources, index.as_ref().map(|x| x.as_str()))
+                    .chain_err(|| format!("can't lock {}", name))?;
+                indexes.insert(canonical_name(name), source.name.clone());
+                reqs.push(
+                    PackageVersionReq::from_specifier(name, version)?
+                        .with_extras(package_info.extras.clone())
+                        .with_markers(package_info.markers.clone()),
+                );
+                continue;
+            }
+            PackageSource::Path { ref path, editable } => unresolved(LockedPackage {
+                path: Some(path.to_owned()),
+                editable: if editable { Some(true) } else { None },
+                ..Default::default()
+            }),
+            PackageSource::File { ref file } => unresolved(LockedPackage {
+                file: Some(file.to_owned()),
+                ..Default::default()
+            }),
+            PackageSource::Git {
+                ref git,
+                ref ref_,
+                ref subdirectory,
+                editable,
+            } => unresolved(LockedPackage {
+                git: Some(git.to_owned()),
+                ref_: ref_.clone(),
+                subdirectory: subdirectory.clone(),
+                editable: if editable { Some(true) } else { None },
+                ..Default::default()
+            }),
+        };
+        locked.insert(canonical_name(name), locked_package);
+    }
+
+    // Dependencies come from the first source, as in pipenv
+    let default_index = if reqs.is_empty() {
+        None
+    } else {
+        index_source(sources, None)
+            .chain_err(|| "can't lock dependencies")?
+            .name
+            .clone()
+    };
+    let mut resolver = Resolver::new(client, target, allow_prereleases, limits);
+    for (name, package) in resolver.resolve(reqs)? {
+        let index = indexes
+            .remove(&name)
+            .unwrap_or_else(|| default_index.clone());
+        locked.insert(
+            name,
+            LockedPackage {
+                hashes: package.hashes,
+                version: Some(format!("=={}", package.version_string)),
+                markers: package.markers,
+                extras: package.extras,
+                index: index,
+                ..Default::default()
+            },
+        );
+    }
+    Ok(locked)
+}
+
+/// Resolves each of the Pipfile `categories`, eg. `packages` or `docs`, for
+/// `target` into its Pipfile.lock section. `pipfile_hash` is recorded so that
+/// stale locks can be detected later, see `pipfile::pipfile_hash`. Release
+/// files are unpacked within `limits` to read their dependencies.
+pub fn lock_pipfile(
+    client: &reqwest::Client,
+    target: &TargetPython,
+    pipfile: &Pipfile,
+    pipfile_hash: String,
+    categories: &[String],
+    limits: &ArchiveLimits,
+) -> Result<Lockfile> {
+    let sources = pipfile.sources();
+    let allow_prereleases = pipfile.pipenv.allow_prereleases;
+
+    let mut locked = BTreeMap::new();
+    for category in categories.iter() {
+        let packages = lock_section(
+            client,
+            target,
+            &sources,
+            pipfile.category(category),
+            allow_prereleases,
+            limits,
+        ).chain_err(|| format!("failed to lock {}", category))?;
+        locked.insert(lockfile_category(category).to_owned(), packages);
+    }
+    Ok(Lockfile {
+        categories: locked,
+        meta: LockfileMeta {
+            hash: LockfileMetaHash {
+                sha256: pipfile_hash,
+            },
+            pipfile_spec: Some(PIPFILE_SPEC),
+            requires: pipfile.requires.clone(),
+            sources: sources,
+        },
+    })
+}
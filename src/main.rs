@@ -9,6 +9,7 @@ extern crate lazy_static;
 #[macro_use]
 extern crate serde_derive;
 
+extern crate base64;
 extern crate flate2;
 extern crate rayon;
 extern crate regex;
@@ -16,21 +17,50 @@ extern crate reqwest;
 extern crate semver;
 extern crate serde;
 extern crate serde_json;
+extern crate sha2;
 extern crate tar;
+extern crate tempdir;
 extern crate toml;
+extern crate toml_edit;
 extern crate zip;
 
-use std::fs::File;
+use std::collections::{BTreeMap, HashSet};
+use std::env;
+use std::fs::{self, File};
 use std::io::{stdout, Read, Write};
+use std::path::{Path, PathBuf};
 use rayon::prelude::*;
 
+mod add;
+mod archive;
 mod pipfile;
+mod pipfile_edit;
+mod pipenv_json;
+mod entry_points;
+mod hashing;
+mod install_wheel;
+mod integrity;
+mod installed;
+mod record;
 mod pypi;
 mod parse_release;
 mod release;
+mod resolver;
+mod lock;
+mod markers;
+mod sync;
+mod verify;
+mod interpreters;
 mod semver_utils;
+mod tags;
+mod uninstall;
+mod venv;
+mod manylinux;
+mod wheel;
 mod version_req;
 mod errors;
+#[cfg(test)]
+mod test_support;
 
 use errors::*;
 
@@ -56,54 +86,415 @@ fn main() {
     }
 }
 
-fn get_package_data(client: &reqwest::Client, package_name: &str) -> Result<pypi::PypiPackage> {
-    let mut resp = client
-        .get(&format!(
-            "https://pypi.python.org/pypi/{}/json",
-            package_name
-        ))
-        .send()?;
-    Ok(resp.json()?)
-}
+use pypi::get_package_data;
 
-fn get_file_path_bytes(path: &str) -> Result<Vec<u8>> {
+fn get_file_path_bytes<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
     let mut bytes = vec![];
     let mut file = File::open(path)?;
     file.read_to_end(&mut bytes)?;
     Ok(bytes)
 }
 
+fn parse_pipfile(pipfile_bytes: &[u8]) -> Result<pipfile::Pipfile> {
+    Ok(toml::from_slice(pipfile_bytes).chain_err(|| "failed to parse Pipfile")?)
+}
+
+fn load_pipfile<P: AsRef<Path>>(path: P) -> Result<pipfile::Pipfile> {
+    parse_pipfile(&get_file_path_bytes(path)?)
+}
+
+fn load_lockfile<P: AsRef<Path>>(path: P) -> Result<pipfile::Lockfile> {
+    let lockfile_bytes = get_file_path_bytes(path)?;
+    Ok(serde_json::from_slice(&lockfile_bytes).chain_err(|| "failed to parse Pipfile.lock")?)
+}
+
+fn project_dir(matches: &clap::ArgMatches) -> Result<PathBuf> {
+    match matches.value_of("cwd") {
+        Some(cwd) => Ok(PathBuf::from(cwd)),
+        None => Ok(env::current_dir()?),
+    }
+}
+
+/// The python version to resolve for when neither the Pipfile nor a
+/// `python3` on the PATH says which.
+const DEFAULT_TARGET_PYTHON: &str = "3.6";
+
+/// The python to resolve for: the version the Pipfile requires if it has
+/// one, otherwise whichever `python3` is on the PATH, falling back to
+/// `DEFAULT_TARGET_PYTHON`.
+fn target_python(pipfile: Option<&pipfile::Pipfile>) -> Result<tags::TargetPython> {
+    let python_version = pipfile.and_then(|pipfile| pipfile.requires.python_request());
+    let version = match python_version {
+        Some(version) => version.to_owned(),
+        None => interpreters::python_full_version("python3").unwrap_or_else(|e| {
+            eprintln!(
+                "warning: resolving for python {} as the Pipfile doesn't require a version: {}",
+                DEFAULT_TARGET_PYTHON, e
+            );
+            DEFAULT_TARGET_PYTHON.to_owned()
+        }),
+    };
+    tags::TargetPython::parse(&version, tags::host_platforms()?)
+}
+
+/// The Pipfile categories named by `--categories`, which may be separated by
+/// spaces or commas, eg. `--categories "packages docs"`.
+fn selected_categories(matches: &clap::ArgMatches) -> Option<Vec<String>> {
+    matches.value_of("categories").map(|categories| {
+        categories
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|x| x != &"")
+            .map(|x| x.to_owned())
+            .collect()
+    })
+}
+
+/// Handles `--python`: finds the interpreter it asks for and requires its
+/// version in the project's Pipfile, creating the Pipfile if needed.
+fn select_python(project_dir: &Path, request: &str) -> Result<interpreters::InterpreterInfo> {
+    let interpreter = interpreters::find_python(request)?;
+    let pipfile_path = project_dir.join("Pipfile");
+    let mut editor = pipfile_edit::PipfileEditor::parse(&read_pipfile_contents(&pipfile_path)?)?;
+    // Only pin the full version when that's what was asked for
+    let full_version = if !request.contains('/') && request.split('.').count() >= 3 {
+        Some(interpreter.version.as_str())
+    } else {
+        None
+    };
+    editor.set_python_requirement(
+        &interpreters::short_version(&interpreter.version),
+        full_version,
+    )?;
+    File::create(&pipfile_path)?.write_all(editor.to_string().as_bytes())?;
+    println!(
+        "using {} ({})",
+        interpreter.executable.display(),
+        interpreter.version
+    );
+    Ok(interpreter)
+}
+
+/// The Pipfile category `add` or `remove` edits: `--category`, `--dev` for
+/// `dev-packages`, or `packages`.
+fn edited_category(matches: &clap::ArgMatches) -> String {
+    match matches.value_of("category") {
+        Some(category) => category.to_owned(),
+        None if matches.is_present("dev") => "dev-packages".to_owned(),
+        None => "packages".to_owned(),
+    }
+}
+
+/// The Pipfile's contents, empty if the project doesn't have one yet.
+fn read_pipfile_contents(path: &Path) -> Result<String> {
+    if !path.exists() {
+        return Ok(String::new());
+    }
+    String::from_utf8(get_file_path_bytes(path)?).chain_err(|| "Pipfile isn't valid utf-8")
+}
+
+/// Resolves the project's Pipfile and writes Pipfile.lock. When only some
+/// `categories` are relocked the others keep whatever they were locked to,
+/// which must still match the Pipfile since the lock records its hash.
+fn lock_project(
+    client: &reqwest::Client,
+    limits: &archive::ArchiveLimits,
+    project_dir: &Path,
+    categories: Option<Vec<String>>,
+) -> Result<()> {
+    let pipfile_bytes = get_file_path_bytes(project_dir.join("Pipfile"))?;
+    let pipfile_inst = parse_pipfile(&pipfile_bytes)?;
+    let pipfile_hash = pipfile::pipfile_hash(&pipfile_bytes)?;
+    let target = target_python(Some(&pipfile_inst))?;
+    let categories = categories.unwrap_or_else(|| pipfile_inst.category_names());
+    let lockfile_path = project_dir.join("Pipfile.lock");
+    let previous = if lockfile_path.exists() {
+        Some(load_lockfile(&lockfile_path)?)
+    } else {
+        None
+    };
+    let unselected: Vec<String> = pipfile_inst
+        .category_names()
+        .into_iter()
+        .filter(|category| !categories.contains(category))
+        .collect();
+    let mut resolver = resolver::Resolver::new(
+        client,
+        &target,
+        pipfile_inst.pipenv.allow_prereleases,
+        limits,
+    );
+    let problems = verify::category_problems(
+        &pipfile_inst,
+        previous.as_ref(),
+        &unselected,
+        |name, version| resolver.requires(name, version),
+    )?;
+    for problem in problems.iter() {
+        eprintln!("{}", problem);
+    }
+    if !problems.is_empty() {
+        bail!("Pipfile.lock is also out of date for categories that weren't selected");
+    }
+    let mut lockfile = lock::lock_pipfile(
+        client,
+        &target,
+        &pipfile_inst,
+        pipfile_hash,
+        &categories,
+        limits,
+    )?;
+    // Only the Pipfile's other categories are kept from the previous lock
+    if let Some(mut previous) = previous {
+        for category in unselected.iter() {
+            let section = pipfile::lockfile_category(category);
+            if let Some(packages) = previous.categories.remove(section) {
+                lockfile.categories.insert(section.to_owned(), packages);
+            }
+        }
+    }
+    File::create(&lockfile_path)?.write_all(lockfile.to_json_string()?.as_bytes())?;
+    for category in categories.iter() {
+        println!(
+            "locked {} packages in [{}]",
+            lockfile.categories[pipfile::lockfile_category(category)].len(),
+            category
+        );
+    }
+    Ok(())
+}
+
+/// The Pipfile categories `sync` or `install` installs: `--categories`, or
+/// `packages` plus `dev-packages` with `--dev`.
+fn installed_categories(matches: &clap::ArgMatches) -> Vec<String> {
+    selected_categories(matches).unwrap_or_else(|| {
+        let mut categories = vec!["packages".to_owned()];
+        if matches.is_present("dev") {
+            categories.push("dev-packages".to_owned());
+        }
+        categories
+    })
+}
+
+/// The project's virtualenv and its interpreter, creating the virtualenv
+/// if there isn't one yet. It's created with `python`, the interpreter
+/// `--python` selected, or else with the python the Pipfile requires. An
+/// existing virtualenv for another version is recreated for `python`, and
+/// refused if it doesn't match the Pipfile.
+fn project_virtualenv(
+    project_dir: &Path,
+    pipfile: &pipfile::Pipfile,
+    python: Option<&interpreters::InterpreterInfo>,
+) -> Result<(venv::Virtualenv, interpreters::InterpreterInfo)> {
+    let settings = venv::VirtualenvSettings::from_env()?;
+    let location = venv::virtualenv_location(project_dir, &settings)?;
+    if location.join("pyvenv.cfg").exists() {
+        let virtualenv = venv::Virtualenv::open(&location)?;
+        let is_active = settings.active_virtualenv.as_ref() == Some(&location);
+        match (python, pipfile.requires.python_request()) {
+            (Some(python), _) if python.version != virtualenv.version && is_active => {
+                bail!(
+                    "the activated virtualenv at {} uses python {}, deactivate it to use {}",
+                    location.display(),
+                    virtualenv.version,
+                    python.version
+                );
+            }
+            (Some(python), _) if python.version != virtualenv.version => {
+                println!(
+                    "removing virtualenv at {}, which uses python {}",
+                    location.display(),
+                    virtualenv.version
+                );
+                fs::remove_dir_all(&location)?;
+            }
+            (None, Some(request))
+                if !interpreters::version_matches(&virtualenv.version, request) =>
+            {
+                bail!(
+                    "the virtualenv at {} uses python {} but the Pipfile requires {}, \
+                     use --python to recreate it",
+                    location.display(),
+                    virtualenv.version,
+                    request
+                );
+            }
+            _ => {
+                let interpreter = interpreters::query_interpreter(virtualenv.python())?;
+                return Ok((virtualenv, interpreter));
+            }
+        }
+    }
+    let interpreter = match python {
+        Some(python) => python.clone(),
+        None => interpreters::find_python(pipfile.requires.python_request().unwrap_or("3"))?,
+    };
+    let prompt = fs::canonicalize(project_dir)?
+        .file_name()
+        .map_or("venv".to_owned(), |x| x.to_string_lossy().into_owned());
+    let virtualenv = venv::Virtualenv::create(&location, &interpreter, &prompt, true)?;
+    println!(
+        "created virtualenv at {} using {} ({})",
+        location.display(),
+        interpreter.executable.display(),
+        interpreter.version
+    );
+    Ok((virtualenv, interpreter))
+}
+
+/// The interpreter `install --deploy` checks the lock against and installs
+/// with: `python` from `--python`, else the existing virtualenv's, else the
+/// one the Pipfile requires. Nothing is created or removed.
+fn deployment_python(
+    project_dir: &Path,
+    pipfile: &pipfile::Pipfile,
+    python: Option<&interpreters::InterpreterInfo>,
+) -> Result<interpreters::InterpreterInfo> {
+    if let Some(python) = python {
+        return Ok(python.clone());
+    }
+    let location =
+        venv::virtualenv_location(project_dir, &venv::VirtualenvSettings::from_env()?)?;
+    if location.join("pyvenv.cfg").exists() {
+        return interpreters::query_interpreter(venv::Virtualenv::open(&location)?.python());
+    }
+    interpreters::find_python(pipfile.requires.python_request().unwrap_or("3"))
+}
+
+/// The project's virtualenv, failing if it hasn't been created yet.
+fn existing_virtualenv(project_dir: &Path) -> Result<venv::Virtualenv> {
+    let location =
+        venv::virtualenv_location(project_dir, &venv::VirtualenvSettings::from_env()?)?;
+    if !location.exists() {
+        bail!(
+            "no virtualenv has been created for this project yet, it would be at {}",
+            location.display()
+        );
+    }
+    venv::Virtualenv::open(&location)
+}
+
+/// Installs exactly what Pipfile.lock pins for `categories` into the
+/// project's virtualenv.
+fn sync_project(
+    client: &reqwest::Client,
+    limits: &archive::ArchiveLimits,
+    project_dir: &Path,
+    categories: &[String],
+    python: Option<&interpreters::InterpreterInfo>,
+) -> Result<()> {
+    let pipfile_inst = load_pipfile(project_dir.join("Pipfile"))?;
+    let lockfile = load_lockfile(project_dir.join("Pipfile.lock"))?;
+    let (virtualenv, interpreter) = project_virtualenv(project_dir, &pipfile_inst, python)?;
+    let requested: HashSet<String> = categories
+        .iter()
+        .flat_map(|category| pipfile_inst.category(category).keys())
+        .map(|name| pypi::canonical_name(name))
+        .collect();
+    let packages = sync::locked_packages(&lockfile, categories, &interpreter)?;
+    let summary =
+        sync::sync(client, &virtualenv, &interpreter, &packages, &requested, limits)?;
+    println!(
+        "{} installed, {} already up to date, {} skipped",
+        summary.installed, summary.up_to_date, summary.skipped
+    );
+    Ok(())
+}
+
+/// Fails unless the project's Pipfile.lock can be deployed as-is with `python`.
+fn verify_project<P: AsRef<Path>>(project_dir: &Path, python: P) -> Result<()> {
+    let pipfile_bytes = get_file_path_bytes(project_dir.join("Pipfile"))?;
+    let pipfile_inst = parse_pipfile(&pipfile_bytes)?;
+    let lockfile = load_lockfile(project_dir.join("Pipfile.lock"))?;
+    let python_full_version = interpreters::python_full_version(python)?;
+
+    let problems = verify::lock_problems(
+        &pipfile_bytes,
+        &pipfile_inst,
+        &lockfile,
+        Some(&python_full_version),
+    )?;
+    for problem in problems.iter() {
+        eprintln!("{}", problem);
+    }
+    if !problems.is_empty() {
+        bail!(ErrorKind::LockfileOutOfDate(problems.len()));
+    }
+    Ok(())
+}
+
 fn run() -> Result<()> {
     let yaml = load_yaml!("cli.yml");
     let matches = clap::App::from_yaml(yaml).get_matches();
     let client = reqwest::Client::new();
+    let limits = archive::ArchiveLimits::from_env()?;
 
+    // Only what creates or installs into the virtualenv takes `--python`
+    let python = match matches.value_of("python") {
+        Some(request) => {
+            match matches.subcommand_name() {
+                None | Some("install") | Some("sync") => (),
+                Some(name) => bail!("--python can only be used with install or sync, not {}", name),
+            }
+            Some(select_python(&project_dir(&matches)?, request)?)
+        }
+        None => None,
+    };
+    if matches.is_present("venv") {
+        println!("{}", existing_virtualenv(&project_dir(&matches)?)?.prefix.display());
+        return Ok(());
+    }
+    if let (Some(python), None) = (python.as_ref(), matches.subcommand_name()) {
+        let project_dir = project_dir(&matches)?;
+        let pipfile_inst = load_pipfile(project_dir.join("Pipfile"))?;
+        project_virtualenv(&project_dir, &pipfile_inst, Some(python))?;
+    }
     if let Some(matches) = matches.subcommand_matches("info") {
         let package_name = matches.value_of("PACKAGE_NAME").unwrap();
+        let target = target_python(None)?;
         let package_data = get_package_data(&client, &package_name)?;
-        let latest_version = package_data.latest_version()?;
+        let latest_version = package_data.latest_version(&target)?;
         println!("latest version: {:?}", latest_version);
         println!(
             "{:?}",
-            package_data.get_requires_for_version(&client, &latest_version)?
+            package_data.get_requires_for_version(&client, &latest_version, &target, &limits)?
         );
+        let entry_points = package_data.get_entry_points_for_version(
+            &client,
+            &latest_version,
+            &target,
+            &limits,
+        )?;
+        for (group, entry_points) in entry_points.groups.iter() {
+            for entry_point in entry_points.iter() {
+                println!("{}: {}", group, entry_point);
+            }
+        }
+        for version in package_data.yanked_versions()? {
+            match package_data.yanked_reason(&version) {
+                Some(reason) => println!("yanked: {} ({})", version, reason),
+                None => println!("yanked: {}", version),
+            }
+        }
     }
     if let Some(matches) = matches.subcommand_matches("pipfile-info") {
-        let pipfile_bytes = get_file_path_bytes(matches.value_of("PIPFILE_PATH").unwrap())?;
-
-        let pipfile_inst: pipfile::Pipfile =
-            toml::from_slice(&pipfile_bytes).chain_err(|| "failed to parse Pipfile")?;
+        let pipfile_inst = load_pipfile(matches.value_of("PIPFILE_PATH").unwrap())?;
+        let target = target_python(Some(&pipfile_inst))?;
 
         pipfile_inst
-            .packages
+            .category("packages")
             .par_iter()
             .map(|(k, _)| k)
             .map(|package_name| {
                 get_package_data(&client, package_name).unwrap()
             })
             .map(|package_datum| {
-                let latest_version = package_datum.latest_version()?;
-                let requires = package_datum.get_requires_for_version(&client, &latest_version)?;
+                let latest_version = package_datum.latest_version(&target)?;
+                let requires = package_datum.get_requires_for_version(
+                    &client,
+                    &latest_version,
+                    &target,
+                    &limits,
+                )?;
 
                 let stdout_ = stdout();
                 let mut handle = stdout_.lock();
@@ -117,10 +508,189 @@ fn run() -> Result<()> {
             .unwrap_or(Ok(()))?;
     }
     if let Some(matches) = matches.subcommand_matches("validate-lockfile") {
-        let lockfile_bytes = get_file_path_bytes(matches.value_of("LOCKFILE_PATH").unwrap())?;
-        let _: pipfile::Lockfile =
-            serde_json::from_slice(&lockfile_bytes).chain_err(|| "failed to parse Pipfile.lock")?;
+        let lockfile = load_lockfile(matches.value_of("LOCKFILE_PATH").unwrap())?;
+        if let Some(pipfile_path) = matches.value_of("pipfile") {
+            if !lockfile.matches_pipfile(&get_file_path_bytes(pipfile_path)?)? {
+                bail!("Pipfile.lock is out of date with {}", pipfile_path);
+            }
+        }
         println!("ok");
     }
+    if let Some(lock_matches) = matches.subcommand_matches("lock") {
+        let project_dir = project_dir(&matches)?;
+        lock_project(&client, &limits, &project_dir, selected_categories(lock_matches))?;
+    }
+    if let Some(add_matches) = matches.subcommand_matches("add") {
+        let project_dir = project_dir(&matches)?;
+        let pipfile_path = project_dir.join("Pipfile");
+        let contents = read_pipfile_contents(&pipfile_path)?;
+        let pipfile_inst = parse_pipfile(contents.as_bytes())?;
+        let target = target_python(Some(&pipfile_inst))?;
+        let category = edited_category(add_matches);
+        let style = add::SpecifierStyle::parse(add_matches.value_of("specifier-style").unwrap())?;
+
+        let mut editor = pipfile_edit::PipfileEditor::parse(&contents)?;
+        for argument in add_matches.values_of("PACKAGES").unwrap() {
+            let (name, mut package_info) = add::parse_package_argument(argument)?;
+            add::infer_specifier(
+                &client,
+                &target,
+                &name,
+                &mut package_info,
+                style,
+                pipfile_inst.pipenv.allow_prereleases,
+            )?;
+            if !editor.update_package(&category, &name, &package_info)? {
+                editor.set_package(&category, &name, &package_info)?;
+            }
+            println!(
+                "added {} {} to [{}]",
+                name,
+                package_info.version_spec().unwrap_or(""),
+                category
+            );
+        }
+        if pipfile_inst.pipenv.sort_pipfile {
+            editor.sort_category(&category);
+        }
+        File::create(&pipfile_path)?.write_all(editor.to_string().as_bytes())?;
+        lock_project(&client, &limits, &project_dir, None)?;
+    }
+    if let Some(remove_matches) = matches.subcommand_matches("remove") {
+        let project_dir = project_dir(&matches)?;
+        let pipfile_path = project_dir.join("Pipfile");
+        let category = edited_category(remove_matches);
+
+        let contents = read_pipfile_contents(&pipfile_path)?;
+        let mut editor = pipfile_edit::PipfileEditor::parse(&contents)?;
+        let mut removed = false;
+        for name in remove_matches.values_of("PACKAGES").unwrap() {
+            if editor.remove_package(&category, name) {
+                println!("removed {} from [{}]", name, category);
+                removed = true;
+            } else {
+                eprintln!("warning: {} isn't in [{}]", name, category);
+            }
+        }
+        if removed {
+            File::create(&pipfile_path)?.write_all(editor.to_string().as_bytes())?;
+            lock_project(&client, &limits, &project_dir, None)?;
+        }
+    }
+    if let Some(sync_matches) = matches.subcommand_matches("sync") {
+        let project_dir = project_dir(&matches)?;
+        let categories = installed_categories(sync_matches);
+        sync_project(&client, &limits, &project_dir, &categories, python.as_ref())?;
+    }
+    if let Some(install_matches) = matches.subcommand_matches("install") {
+        let project_dir = project_dir(&matches)?;
+        let pipfile_bytes = get_file_path_bytes(project_dir.join("Pipfile"))?;
+        let lockfile_path = project_dir.join("Pipfile.lock");
+        let is_stale = !lockfile_path.exists()
+            || !load_lockfile(&lockfile_path)?.matches_pipfile(&pipfile_bytes)?;
+        let mut deployed = None;
+        if install_matches.is_present("deploy") {
+            // The virtualenv is only created once the lock is known to deploy
+            let pipfile_inst = parse_pipfile(&pipfile_bytes)?;
+            let interpreter = deployment_python(&project_dir, &pipfile_inst, python.as_ref())?;
+            verify_project(&project_dir, &interpreter.executable)?;
+            deployed = Some(interpreter);
+        } else if is_stale {
+            println!("Pipfile.lock is out of date, relocking");
+            lock_project(&client, &limits, &project_dir, None)?;
+        }
+        let categories = installed_categories(install_matches);
+        let python = deployed.as_ref().or(python.as_ref());
+        sync_project(&client, &limits, &project_dir, &categories, python)?;
+    }
+    if matches.subcommand_matches("freeze").is_some() {
+        let virtualenv = existing_virtualenv(&project_dir(&matches)?)?;
+        for distribution in installed::installed_distributions(&virtualenv.site_packages())? {
+            println!("{}", distribution.requirement());
+        }
+    }
+    if let Some(clean_matches) = matches.subcommand_matches("clean") {
+        let project_dir = project_dir(&matches)?;
+        let lockfile = load_lockfile(project_dir.join("Pipfile.lock"))?;
+        let virtualenv = existing_virtualenv(&project_dir)?;
+        let distributions = installed::installed_distributions(&virtualenv.site_packages())?;
+        let categories = selected_categories(clean_matches);
+        let categories = categories.as_ref().map(|x| x.as_slice());
+        for distribution in sync::extraneous_distributions(&distributions, &lockfile, categories)? {
+            if distribution.kind != installed::MetadataKind::DistInfo {
+                eprintln!(
+                    "warning: skipping {} {}: it has no RECORD",
+                    distribution.name, distribution.version
+                );
+            } else if clean_matches.is_present("dry-run") {
+                println!("would uninstall {} {}", distribution.name, distribution.version);
+            } else {
+                uninstall::uninstall(distribution, &virtualenv.prefix)?;
+                println!("uninstalled {} {}", distribution.name, distribution.version);
+            }
+        }
+    }
+    if matches.subcommand_matches("check").is_some() {
+        let virtualenv = existing_virtualenv(&project_dir(&matches)?)?;
+        let distributions = installed::installed_distributions(&virtualenv.site_packages())?;
+        for distribution in distributions.iter() {
+            if distribution.kind != installed::MetadataKind::DistInfo {
+                eprintln!(
+                    "warning: skipping {} {}: it has no RECORD",
+                    distribution.name, distribution.version
+                );
+            }
+        }
+        let reports = integrity::check_integrity(&distributions)?;
+        let mut failed = 0;
+        for &(distribution, ref report) in reports.iter() {
+            if report.is_clean() {
+                continue;
+            }
+            failed += 1;
+            println!("{} {}:", distribution.name, distribution.version);
+            if report.unreadable_record {
+                println!("    RECORD couldn't be read");
+            }
+            for path in report.modified.iter() {
+                println!("    modified: {}", path);
+            }
+            for path in report.missing.iter() {
+                println!("    missing: {}", path);
+            }
+            for path in report.unexpected.iter() {
+                println!("    unexpected: {}", path);
+            }
+        }
+        if failed > 0 {
+            bail!(ErrorKind::IntegrityCheckFailed(failed));
+        }
+        println!("{} packages match their RECORD", reports.len());
+    }
+    if let Some(requirements_matches) = matches.subcommand_matches("requirements") {
+        let lockfile = load_lockfile(project_dir(&matches)?.join("Pipfile.lock"))?;
+        let categories = selected_categories(requirements_matches)
+            .unwrap_or_else(|| vec!["packages".to_owned()]);
+        let mut requirements = BTreeMap::new();
+        for category in categories.iter() {
+            let packages = lockfile
+                .category(pipfile::lockfile_category(category))
+                .ok_or_else(|| format!("[{}] isn't in Pipfile.lock", category))?;
+            for (name, package) in packages.iter() {
+                requirements.entry(name).or_insert(package);
+            }
+        }
+        let with_hashes = requirements_matches.is_present("hash");
+        for (name, package) in requirements {
+            println!("{}", package.to_requirement(name, with_hashes));
+        }
+    }
+    if let Some(verify_matches) = matches.subcommand_matches("verify") {
+        verify_project(
+            &project_dir(&matches)?,
+            verify_matches.value_of("python").unwrap(),
+        )?;
+        println!("Pipfile.lock is up to date");
+    }
     Ok(())
 }
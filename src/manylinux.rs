@@ -0,0 +1,191 @@
+use std::env::consts;
+use std::fs;
+use std::process::Command;
+use regex::Regex;
+
+/// The C library the host links python against, with its `(major, minor)` version.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Libc {
+    Glibc(u32, u32),
+    Musl(u32, u32),
+}
+
+fn parse_major_minor(version: &str) -> Option<(u32, u32)> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"(\d+)\.(\d+)").unwrap();
+    }
+    let captures = RE.captures(version)?;
+    Some((captures[1].parse().ok()?, captures[2].parse().ok()?))
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn glibc_version_from_libc() -> Option<String> {
+    use std::ffi::CStr;
+    use std::os::raw::c_char;
+
+    extern "C" {
+        fn gnu_get_libc_version() -> *const c_char;
+    }
+    let version = unsafe { CStr::from_ptr(gnu_get_libc_version()) };
+    version.to_str().ok().map(|x| x.to_owned())
+}
+
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+fn glibc_version_from_libc() -> Option<String> {
+    None
+}
+
+/// When we weren't linked against glibc ourselves, ask `ldd`, whose first
+/// line reads eg. `ldd (GNU libc) 2.31`.
+fn glibc_version_from_ldd() -> Option<String> {
+    let output = Command::new("ldd").arg("--version").output().ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let first_line = stdout.lines().next()?;
+    if first_line.contains("GNU libc") || first_line.contains("GLIBC") {
+        first_line.split_whitespace().last().map(|x| x.to_owned())
+    } else {
+        None
+    }
+}
+
+/// musl's dynamic loader prints its version when run without arguments,
+/// eg. `musl libc (x86_64)\nVersion 1.2.2\n...`, on stderr.
+fn musl_version(arch: &str) -> Option<(u32, u32)> {
+    let loader = format!("/lib/ld-musl-{}.so.1", arch);
+    if fs::metadata(&loader).is_err() {
+        return None;
+    }
+    let output = Command::new(&loader).output().ok()?;
+    parse_musl_loader_output(&String::from_utf8_lossy(&output.stderr))
+}
+
+fn parse_musl_loader_output(output: &str) -> Option<(u32, u32)> {
+    let mut lines = output.lines().map(|x| x.trim()).filter(|x| x != &"");
+    if !lines.next()?.starts_with("musl") {
+        return None;
+    }
+    lines
+        .next()
+        .filter(|line| line.starts_with("Version "))
+        .and_then(parse_major_minor)
+}
+
+pub fn detect_libc(arch: &str) -> Option<Libc> {
+    if let Some((major, minor)) = glibc_version_from_libc()
+        .or_else(glibc_version_from_ldd)
+        .as_ref()
+        .and_then(|x| parse_major_minor(x))
+    {
+        return Some(Libc::Glibc(major, minor));
+    }
+    musl_version(arch).map(|(major, minor)| Libc::Musl(major, minor))
+}
+
+/// The machine architecture as it appears in platform tags.
+pub fn host_arch() -> String {
+    match consts::ARCH {
+        "x86" => "i686",
+        "arm" => "armv7l",
+        "powerpc64" if cfg!(target_endian = "little") => "ppc64le",
+        arch => arch,
+    }.to_owned()
+}
+
+/// Every linux platform tag a host with `libc` on `arch` can install, most
+/// specific first, ending with the generic `linux_<arch>` tag.
+pub fn linux_platforms(libc: Option<Libc>, arch: &str) -> Vec<String> {
+    let mut platforms = vec![];
+    match libc {
+        Some(Libc::Glibc(2, minor)) => {
+            // Before manylinux2014 (glibc 2.17) only x86 had manylinux tags
+            let oldest = if arch == "x86_64" || arch == "i686" { 5 } else { 17 };
+            for glibc_minor in (oldest..minor + 1).rev() {
+                platforms.push(format!("manylinux_2_{}_{}", glibc_minor, arch));
+                let legacy = match glibc_minor {
+                    17 => Some("manylinux2014"),
+                    12 => Some("manylinux2010"),
+                    5 => Some("manylinux1"),
+                    _ => None,
+                };
+                if let Some(legacy) = legacy {
+                    platforms.push(format!("{}_{}", legacy, arch));
+                }
+            }
+        }
+        Some(Libc::Musl(1, minor)) => for musl_minor in (0..minor + 1).rev() {
+            platforms.push(format!("musllinux_1_{}_{}", musl_minor, arch));
+        },
+        _ => {}
+    }
+    platforms.push(format!("linux_{}", arch));
+    platforms
+}
+
+/// The platform tags supported by the machine we're running on.
+pub fn host_platforms() -> Vec<String> {
+    let arch = host_arch();
+    linux_platforms(detect_libc(&arch), &arch)
+}
+
+#[cfg(test)]
+mod tests {
+    use manylinux::*;
+
+    #[test]
+    fn glibc_platforms_include_legacy_aliases() {
+        let platforms = linux_platforms(Some(Libc::Glibc(2, 17)), "x86_64");
+
+        assert_eq!(
+            platforms[..3].to_vec(),
+            vec![
+                "manylinux_2_17_x86_64",
+                "manylinux2014_x86_64",
+                "manylinux_2_16_x86_64",
+            ]
+        );
+        assert_eq!(
+            platforms[platforms.len() - 4..].to_vec(),
+            vec![
+                "manylinux_2_6_x86_64",
+                "manylinux_2_5_x86_64",
+                "manylinux1_x86_64",
+                "linux_x86_64",
+            ]
+        );
+        assert!(platforms.contains(&"manylinux2010_x86_64".to_owned()));
+    }
+
+    #[test]
+    fn glibc_platforms_start_at_2014_for_other_arches() {
+        assert_eq!(
+            linux_platforms(Some(Libc::Glibc(2, 18)), "aarch64"),
+            vec![
+                "manylinux_2_18_aarch64",
+                "manylinux_2_17_aarch64",
+                "manylinux2014_aarch64",
+                "linux_aarch64",
+            ]
+        );
+    }
+
+    #[test]
+    fn musl_platforms() {
+        assert_eq!(
+            linux_platforms(Some(Libc::Musl(1, 2)), "x86_64"),
+            vec![
+                "musllinux_1_2_x86_64",
+                "musllinux_1_1_x86_64",
+                "musllinux_1_0_x86_64",
+                "linux_x86_64",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_musl_loader() {
+        let output = "musl libc (x86_64)\nVersion 1.2.2\nDynamic Program Loader\n";
+
+        assert_eq!(parse_musl_loader_output(output), Some((1, 2)));
+        assert_eq!(parse_musl_loader_output("ld.so: no such file"), None);
+    }
+}
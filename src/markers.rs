@@ -0,0 +1,466 @@
+use std::fmt;
+
+use interpreters::MarkerEnvironment;
+use semver_utils::normalize_and_parse_version_string;
+use version_req::PackageVersionReq;
+use errors::*;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(String),
+    Open,
+    Close,
+}
+
+fn tokenize(markers: &str) -> Result<Vec<Token>> {
+    let invalid = |reason: &str| ErrorKind::InvalidMarker(markers.to_owned(), reason.to_owned());
+    let chars: Vec<char> = markers.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' || c == ')' {
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            let end = chars[i + 1..]
+                .iter()
+                .position(|&x| x == c)
+                .ok_or_else(|| invalid("unterminated string"))?;
+            tokens.push(Token::Quoted(chars[i + 1..i + 1 + end].iter().collect()));
+            i += end + 2;
+        } else if "<>=!~".contains(c) {
+            let op: String = chars[i..]
+                .iter()
+                .take_while(|&&x| "<>=!~".contains(x))
+                .collect();
+            i += op.len();
+            tokens.push(Token::Op(op));
+        } else if c.is_alphanumeric() || c == '_' {
+            let word: String = chars[i..]
+                .iter()
+                .take_while(|&&x| x.is_alphanumeric() || x == '_' || x == '.')
+                .collect();
+            i += word.len();
+            tokens.push(Token::Word(word));
+        } else {
+            bail!(invalid(&format!("unexpected `{}`", c)));
+        }
+    }
+    Ok(tokens)
+}
+
+/// A parsed PEP 508 environment marker, eg.
+/// `python_version < "3.8" and sys_platform == "linux"`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Marker {
+    And(Box<Marker>, Box<Marker>),
+    Or(Box<Marker>, Box<Marker>),
+    /// Either side is a variable name or a quoted string.
+    Compare(Value, String, Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Variable(String),
+    Literal(String),
+}
+
+struct Parser<'a> {
+    markers: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+}
+impl<'a> Parser<'a> {
+    fn error(&self, reason: &str) -> Error {
+        ErrorKind::InvalidMarker(self.markers.to_owned(), reason.to_owned()).into()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek_word(&self, word: &str) -> bool {
+        self.tokens.get(self.position) == Some(&Token::Word(word.to_owned()))
+    }
+
+    fn parse_or(&mut self) -> Result<Marker> {
+        let mut marker = self.parse_and()?;
+        while self.peek_word("or") {
+            self.position += 1;
+            marker = Marker::Or(Box::new(marker), Box::new(self.parse_and()?));
+        }
+        Ok(marker)
+    }
+
+    fn parse_and(&mut self) -> Result<Marker> {
+        let mut marker = self.parse_expression()?;
+        while self.peek_word("and") {
+            self.position += 1;
+            marker = Marker::And(Box::new(marker), Box::new(self.parse_expression()?));
+        }
+        Ok(marker)
+    }
+
+    fn parse_expression(&mut self) -> Result<Marker> {
+        if self.tokens.get(self.position) == Some(&Token::Open) {
+            self.position += 1;
+            let marker = self.parse_or()?;
+            if self.next() != Some(Token::Close) {
+                return Err(self.error("expected `)`"));
+            }
+            return Ok(marker);
+        }
+        let left = self.parse_value()?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            Some(Token::Word(ref word)) if word == "in" => "in".to_owned(),
+            Some(Token::Word(ref word)) if word == "not" && self.peek_word("in") => {
+                self.position += 1;
+                "not in".to_owned()
+            }
+            _ => return Err(self.error("expected a comparison operator")),
+        };
+        match op.as_str() {
+            "<" | "<=" | "==" | "!=" | ">=" | ">" | "~=" | "===" | "in" | "not in" => (),
+            _ => return Err(self.error(&format!("unknown operator `{}`", op))),
+        }
+        let right = self.parse_value()?;
+        Ok(Marker::Compare(left, op, right))
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        match self.next() {
+            Some(Token::Quoted(string)) => Ok(Value::Literal(string)),
+            Some(Token::Word(word)) => {
+                // Names from before PEP 508 that setuptools still accepts
+                let name = match word.as_str() {
+                    "os.name" => "os_name",
+                    "sys.platform" => "sys_platform",
+                    "platform.version" => "platform_version",
+                    "platform.machine" => "platform_machine",
+                    "platform.python_implementation" | "python_implementation" => {
+                        "platform_python_implementation"
+                    }
+                    name => name,
+                };
+                Ok(Value::Variable(name.to_owned()))
+            }
+            _ => Err(self.error("expected a variable or a quoted string")),
+        }
+    }
+}
+
+impl Marker {
+    pub fn parse(markers: &str) -> Result<Marker> {
+        let mut parser = Parser {
+            markers: markers,
+            tokens: tokenize(markers)?,
+            position: 0,
+        };
+        let marker = parser.parse_or()?;
+        if parser.position < parser.tokens.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(marker)
+    }
+
+    /// Whether this marker holds in `env` when installing with `extras`. A
+    /// marker mentioning `extra` holds if it does for any one of them.
+    pub fn evaluate(&self, env: &MarkerEnvironment, extras: &[String]) -> Result<bool> {
+        if extras.is_empty() {
+            return self.evaluate_for_extra(env, "");
+        }
+        for extra in extras.iter() {
+            if self.evaluate_for_extra(env, extra)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Decides this marker's `extra` comparisons for a package installed
+    /// with `extras`, keeping the rest to be evaluated where it's installed.
+    pub fn with_extras_decided(&self, extras: &[String]) -> Result<Remaining> {
+        Ok(match *self {
+            Marker::And(ref left, ref right) => match (
+                left.with_extras_decided(extras)?,
+                right.with_extras_decided(extras)?,
+            ) {
+                (Remaining::Never, _) | (_, Remaining::Never) => Remaining::Never,
+                (Remaining::Always, other) | (other, Remaining::Always) => other,
+                (Remaining::When(left), Remaining::When(right)) => {
+                    Remaining::When(Marker::And(Box::new(left), Box::new(right)))
+                }
+            },
+            Marker::Or(ref left, ref right) => match (
+                left.with_extras_decided(extras)?,
+                right.with_extras_decided(extras)?,
+            ) {
+                (Remaining::Always, _) | (_, Remaining::Always) => Remaining::Always,
+                (Remaining::Never, other) | (other, Remaining::Never) => other,
+                (Remaining::When(left), Remaining::When(right)) => {
+                    Remaining::When(Marker::Or(Box::new(left), Box::new(right)))
+                }
+            },
+            Marker::Compare(ref left, _, ref right) => {
+                let extra = Value::Variable("extra".to_owned());
+                if *left != extra && *right != extra {
+                    return Ok(Remaining::When(self.clone()));
+                }
+                // Only `extra` is looked up, so the environment doesn't matter
+                if self.evaluate(&MarkerEnvironment::default(), extras)? {
+                    Remaining::Always
+                } else {
+                    Remaining::Never
+                }
+            }
+        })
+    }
+
+    fn evaluate_for_extra(&self, env: &MarkerEnvironment, extra: &str) -> Result<bool> {
+        match *self {
+            Marker::And(ref left, ref right) => Ok(
+                left.evaluate_for_extra(env, extra)? && right.evaluate_for_extra(env, extra)?,
+            ),
+            Marker::Or(ref left, ref right) => Ok(
+                left.evaluate_for_extra(env, extra)? || right.evaluate_for_extra(env, extra)?,
+            ),
+            Marker::Compare(ref left, ref op, ref right) => {
+                let is_extra = |value: &Value| *value == Value::Variable("extra".to_owned());
+                let (left, right) = if is_extra(left) || is_extra(right) {
+                    (
+                        canonical_extra(&resolve(left, env, extra)?),
+                        canonical_extra(&resolve(right, env, extra)?),
+                    )
+                } else {
+                    (resolve(left, env, extra)?, resolve(right, env, extra)?)
+                };
+                Ok(compare(&left, op, &right))
+            }
+        }
+    }
+}
+
+/// What is left of a marker once its `extra` comparisons are decided.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Remaining {
+    Always,
+    Never,
+    When(Marker),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Variable(ref name) => write!(f, "{}", name),
+            Value::Literal(ref string) if string.contains('"') => write!(f, "'{}'", string),
+            Value::Literal(ref string) => write!(f, "\"{}\"", string),
+        }
+    }
+}
+
+impl fmt::Display for Marker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Marker::And(ref left, ref right) => {
+                for (i, side) in [left, right].iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " and ")?;
+                    }
+                    match ***side {
+                        Marker::Or(..) => write!(f, "({})", side)?,
+                        _ => write!(f, "{}", side)?,
+                    }
+                }
+                Ok(())
+            }
+            Marker::Or(ref left, ref right) => write!(f, "{} or {}", left, right),
+            Marker::Compare(ref left, ref op, ref right) => {
+                write!(f, "{} {} {}", left, op, right)
+            }
+        }
+    }
+}
+
+fn canonical_extra(extra: &str) -> String {
+    extra.replace('_', "-").replace('.', "-").to_lowercase()
+}
+
+fn resolve(value: &Value, env: &MarkerEnvironment, extra: &str) -> Result<String> {
+    let name = match *value {
+        Value::Literal(ref string) => return Ok(string.to_owned()),
+        Value::Variable(ref name) => name,
+    };
+    Ok(match name.as_str() {
+        "implementation_name" => &env.implementation_name,
+        "implementation_version" => &env.implementation_version,
+        "os_name" => &env.os_name,
+        "platform_machine" => &env.platform_machine,
+        "platform_python_implementation" => &env.platform_python_implementation,
+        "platform_release" => &env.platform_release,
+        "platform_system" => &env.platform_system,
+        "platform_version" => &env.platform_version,
+        "python_full_version" => &env.python_full_version,
+        "python_version" => &env.python_version,
+        "sys_platform" => &env.sys_platform,
+        "extra" => extra,
+        _ => bail!(ErrorKind::InvalidMarker(
+            name.to_owned(),
+            "unknown variable".to_owned()
+        )),
+    }.to_owned())
+}
+
+/// Compares as PEP 440 versions when both sides are versions, and falls
+/// back to python's string comparison otherwise.
+fn compare(left: &str, op: &str, right: &str) -> bool {
+    match op {
+        "in" => return right.contains(left),
+        "not in" => return !right.contains(left),
+        "===" => return left == right,
+        _ => (),
+    }
+    let version = normalize_and_parse_version_string(left);
+    let req = PackageVersionReq::from_specifier("marker", &format!("{}{}", op, right));
+    if let (Ok(version), Ok(req)) = (version, req) {
+        return req.matches_with_prereleases(&version, true);
+    }
+    match op {
+        "<" => left < right,
+        "<=" => left <= right,
+        "==" => left == right,
+        "!=" => left != right,
+        ">=" => left >= right,
+        ">" => left > right,
+        _ => false,
+    }
+}
+
+/// The markers of a group of requirements that only apply with `extra`
+/// and where `environment` holds, as requires.txt sections such as
+/// `[socks:sys_platform == "win32"]` and metadata.json groups declare them.
+pub fn group_markers(extra: Option<&str>, environment: Option<&str>) -> Option<String> {
+    let extra = extra.filter(|x| x.trim() != "");
+    let environment = environment.map(|x| x.trim()).filter(|x| x != &"");
+    match (extra, environment) {
+        (Some(extra), Some(environment)) => Some(format!(
+            "({}) and extra == \"{}\"",
+            environment,
+            extra.trim()
+        )),
+        (Some(extra), None) => Some(format!("extra == \"{}\"", extra.trim())),
+        (None, environment) => environment.map(|x| x.to_owned()),
+    }
+}
+
+/// Whether the `markers` of a requirement or locked package hold in `env`.
+/// Packages without markers apply everywhere.
+pub fn markers_apply(markers: Option<&str>, env: &MarkerEnvironment) -> Result<bool> {
+    match markers {
+        Some(markers) if markers.trim() != "" => Marker::parse(markers)?.evaluate(env, &[]),
+        _ => Ok(true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use interpreters::MarkerEnvironment;
+    use markers::*;
+
+    fn linux_env() -> MarkerEnvironment {
+        MarkerEnvironment {
+            os_name: "posix".to_owned(),
+            sys_platform: "linux".to_owned(),
+            platform_system: "Linux".to_owned(),
+            platform_machine: "x86_64".to_owned(),
+            implementation_name: "cpython".to_owned(),
+            platform_python_implementation: "CPython".to_owned(),
+            python_version: "3.10".to_owned(),
+            python_full_version: "3.10.4".to_owned(),
+            ..MarkerEnvironment::default()
+        }
+    }
+
+    fn holds(markers: &str) -> bool {
+        Marker::parse(markers).unwrap().evaluate(&linux_env(), &[]).unwrap()
+    }
+
+    #[test]
+    fn evaluate_markers() {
+        assert!(holds("sys_platform == 'linux'"));
+        assert!(!holds("os_name == \"nt\""));
+        assert!(holds("python_version >= '3.6'"));
+        assert!(holds("python_version > '3.9'"));
+        assert!(!holds("python_version < '3.8'"));
+        assert!(holds("'3.8' < python_version"));
+        assert!(holds("python_full_version == '3.10.*'"));
+        assert!(holds("'linux' in sys_platform and platform_machine not in 'arm64 aarch64'"));
+        // `and` binds tighter than `or`
+        assert!(holds("os_name == 'nt' or (os_name == 'posix' and python_version != '2.7')"));
+        assert!(!holds("os_name == 'nt' or os_name == 'posix' and python_version == '2.7'"));
+        assert!(holds("python_implementation == 'CPython'"));
+    }
+
+    #[test]
+    fn evaluate_extras() {
+        let marker = Marker::parse("extra == 'Socks_Proxy' and os_name == 'posix'").unwrap();
+
+        assert!(!marker.evaluate(&linux_env(), &[]).unwrap());
+        assert!(marker.evaluate(&linux_env(), &["socks-proxy".to_owned()]).unwrap());
+        assert!(!marker.evaluate(&linux_env(), &["security".to_owned()]).unwrap());
+    }
+
+    #[test]
+    fn decide_extras() {
+        let marker = Marker::parse(
+            "python_version < '3.8' and (extra == 'socks' or extra == 'security')",
+        ).unwrap();
+
+        assert_eq!(marker.with_extras_decided(&[]).unwrap(), Remaining::Never);
+        assert_eq!(
+            marker.with_extras_decided(&["socks".to_owned()]).unwrap(),
+            Remaining::When(Marker::parse("python_version < '3.8'").unwrap())
+        );
+        assert_eq!(
+            Marker::parse("extra == 'socks'")
+                .unwrap()
+                .with_extras_decided(&["socks".to_owned()])
+                .unwrap(),
+            Remaining::Always
+        );
+    }
+
+    #[test]
+    fn display_markers() {
+        let marker = Marker::parse(
+            "(os_name=='nt' or sys_platform=='cygwin') and python_version<'3' or 'a\"b' in os_name",
+        ).unwrap();
+
+        assert_eq!(
+            marker.to_string(),
+            "(os_name == \"nt\" or sys_platform == \"cygwin\") and python_version < \"3\" \
+             or 'a\"b' in os_name"
+        );
+        assert_eq!(Marker::parse(&marker.to_string()).unwrap(), marker);
+    }
+
+    #[test]
+    fn reject_invalid_markers() {
+        for markers in &["python_version", "python_version >= '3", "os_name = 'nt'",
+                         "(os_name == 'nt'", "os_name == 'nt' or", "shoe_size == '9'"] {
+            let result = Marker::parse(markers).and_then(|x| x.evaluate(&linux_env(), &[]));
+            match *result.unwrap_err().kind() {
+                ErrorKind::InvalidMarker(..) => (),
+                ref kind => panic!("unexpected error {:?} for {}", kind, markers),
+            }
+        }
+        assert!(markers_apply(None, &linux_env()).unwrap());
+    }
+}
@@ -1,78 +1,172 @@
 use std::io;
-use std::io::Read;
 use flate2::read::GzDecoder;
 use tar::Archive as TarArchive;
 use zip::read::ZipArchive;
 use serde_json;
 
+use archive::{ArchiveGuard, ArchiveLimits};
+use entry_points::EntryPoints;
+use markers::group_markers;
 use version_req::PackageVersionReq;
 use release::{ReleaseType, WheelMetadata};
 use errors::*;
 
-fn get_wheel_metadata_from_archive_file<R>(mut file: R) -> Result<WheelMetadata>
+fn get_wheel_requirements_from_archive_file<R>(
+    mut file: R,
+    limits: &ArchiveLimits,
+) -> Result<Vec<PackageVersionReq>>
 where
     R: io::Read,
 {
+    let mut guard = ArchiveGuard::new(limits);
     let mut archive = {
         let mut bytes = vec![];
         file.read_to_end(&mut bytes)?;
         ZipArchive::new(io::Cursor::new(bytes))?
     };
+    let mut metadata_index = None;
     for i in 0..archive.len() {
         let file = archive.by_index(i)?;
-        if file.name().ends_with(".dist-info/metadata.json") {
-            println!("parsing {}", file.name());
-            let wheel_meta: WheelMetadata = serde_json::from_reader(file)?;
-            return Ok(wheel_meta);
+        let name = file.name().to_owned();
+        guard.entry(&name, file.size())?;
+        if name.ends_with(".dist-info/metadata.json") {
+            let wheel_meta: WheelMetadata = serde_json::from_slice(&guard.read(&name, file)?)?;
+            return wheel_meta.to_version_reqs();
+        }
+        if name.ends_with(".dist-info/METADATA") {
+            metadata_index = Some(i);
         }
     }
+    // Wheels built by modern tooling only ship the RFC 822 style METADATA file
+    if let Some(i) = metadata_index {
+        let file = archive.by_index(i)?;
+        let name = file.name().to_owned();
+        return parse_metadata_requires_dist(&guard.read_to_string(&name, file)?);
+    }
     bail!(ErrorKind::ArchiveFileNotFound(
         ".dist-info/metadata.json".to_owned()
     ));
 }
 
-fn parse_requires_txt(text: &str) -> Result<Vec<PackageVersionReq>> {
-    text.split("\n")
-        .filter(|line| line != &"")
-        .take_while(|line| !line.starts_with("["))
-        .map(|line| PackageVersionReq::parse_requirement(line))
+/// Every `Requires-Dist` of METADATA, including those behind markers.
+fn parse_metadata_requires_dist(text: &str) -> Result<Vec<PackageVersionReq>> {
+    text.lines()
+        .take_while(|line| line != &"")
+        .filter(|line| line.starts_with("Requires-Dist:"))
+        .map(|line| PackageVersionReq::parse_requirement(&line["Requires-Dist:".len()..]))
         .collect()
 }
 
+/// The requirements of an sdist's requires.txt. Those in sections such as
+/// `[socks]` or `[:python_version < "3"]` carry the section as markers.
+fn parse_requires_txt(text: &str) -> Result<Vec<PackageVersionReq>> {
+    let mut section = None;
+    let mut reqs = vec![];
+    for line in text.lines().map(|x| x.trim()).filter(|x| x != &"") {
+        if line.starts_with('[') && line.ends_with(']') {
+            let header = &line[1..line.len() - 1];
+            section = match header.find(':') {
+                Some(i) => group_markers(Some(&header[..i]), Some(&header[i + 1..])),
+                None => group_markers(Some(header), None),
+            };
+            continue;
+        }
+        let req = PackageVersionReq::parse_requirement(line)?;
+        let markers = match (section.as_ref(), req.markers()) {
+            (Some(section), Some(markers)) => Some(format!("({}) and ({})", section, markers)),
+            (section, markers) => section.map(|x| x.as_str()).or(markers).map(|x| x.to_owned()),
+        };
+        reqs.push(req.with_markers(markers));
+    }
+    Ok(reqs)
+}
+
 pub fn parse_release_requirements<R>(
     file: R,
     release_type: ReleaseType,
+    limits: &ArchiveLimits,
 ) -> Result<Vec<PackageVersionReq>>
 where
     R: io::Read,
 {
+    match release_type {
+        ReleaseType::BdistWheel => get_wheel_requirements_from_archive_file(file, limits),
+        ReleaseType::Sdist => {
+            match read_archive_member(file, release_type, ".egg-info/requires.txt", limits)? {
+                Some(requires_txt) => parse_requires_txt(&requires_txt),
+                None => bail!(ErrorKind::ArchiveFileNotFound(
+                    ".egg-info/requires.txt".to_owned()
+                )),
+            }
+        }
+        _ => bail!(ErrorKind::UnsupportedReleaseType(release_type)),
+    }
+}
+
+/// The contents of the first archive member whose name ends with `suffix`.
+fn read_archive_member<R>(
+    mut file: R,
+    release_type: ReleaseType,
+    suffix: &str,
+    limits: &ArchiveLimits,
+) -> Result<Option<String>>
+where
+    R: io::Read,
+{
+    let mut guard = ArchiveGuard::new(limits);
     match release_type {
         ReleaseType::BdistWheel => {
-            let wheel_meta = get_wheel_metadata_from_archive_file(file)?;
-            wheel_meta.to_version_reqs()
+            let mut bytes = vec![];
+            file.read_to_end(&mut bytes)?;
+            let mut archive = ZipArchive::new(io::Cursor::new(bytes))?;
+            for i in 0..archive.len() {
+                let member = archive.by_index(i)?;
+                let name = member.name().to_owned();
+                guard.entry(&name, member.size())?;
+                if name.ends_with(suffix) {
+                    return Ok(Some(guard.read_to_string(&name, member)?));
+                }
+            }
         }
         ReleaseType::Sdist => {
             let mut archive = TarArchive::new(GzDecoder::new(file)?);
             for entry in archive.entries()? {
-                let mut entry = entry?;
-                if entry
-                    .path()?
-                    .to_string_lossy()
-                    .ends_with(".egg-info/requires.txt")
-                {
-                    let requires_txt = {
-                        let mut data = String::new();
-                        entry.read_to_string(&mut data)?;
-                        data
-                    };
-                    return Ok(parse_requires_txt(&requires_txt)?);
+                let entry = entry?;
+                let name = entry.path()?.to_string_lossy().into_owned();
+                guard.entry(&name, entry.header().size()?)?;
+                let entry_type = entry.header().entry_type();
+                if entry_type.is_symlink() || entry_type.is_hard_link() {
+                    let target = entry.link_name()?.unwrap_or_default().into_owned();
+                    guard.link(&name, &target, entry_type.is_hard_link())?;
+                    continue;
+                }
+                if name.ends_with(suffix) {
+                    return Ok(Some(guard.read_to_string(&name, entry)?));
                 }
             }
-            bail!(ErrorKind::ArchiveFileNotFound(
-                ".egg-info/requires.txt".to_owned()
-            ));
         }
-        _ => unimplemented!(),
+        _ => bail!(ErrorKind::UnsupportedReleaseType(release_type)),
+    }
+    Ok(None)
+}
+
+/// The entry points a release declares, empty if it has no
+/// `entry_points.txt`.
+pub fn parse_release_entry_points<R>(
+    file: R,
+    release_type: ReleaseType,
+    limits: &ArchiveLimits,
+) -> Result<EntryPoints>
+where
+    R: io::Read,
+{
+    let suffix = match release_type {
+        ReleaseType::BdistWheel => ".dist-info/entry_points.txt",
+        _ => ".egg-info/entry_points.txt",
+    };
+    match read_archive_member(file, release_type, suffix, limits)? {
+        Some(text) => EntryPoints::parse(&text),
+        None => Ok(EntryPoints::default()),
     }
 }
 
@@ -98,7 +192,10 @@ urllib3>=1.21.1,<1.22
 certifi>=2017.4.17
 
 [security]
-pyOpenSSL>=0.14";
+pyOpenSSL>=0.14
+
+[:python_version < \"3\"]
+ipaddress";
 
         let version_reqs = parse_requires_txt(requires_txt).unwrap();
 
@@ -109,7 +206,57 @@ pyOpenSSL>=0.14";
                 make_version_req("idna", vec![">= 2.5", "< 2.6"]),
                 make_version_req("urllib3", vec![">= 1.21.1", "< 1.22"]),
                 make_version_req("certifi", vec![">= 2017.4.17"]),
+                make_version_req("pyOpenSSL", vec![">= 0.14"])
+                    .with_markers(Some("extra == \"security\"".to_owned())),
+                make_version_req("ipaddress", vec![])
+                    .with_markers(Some("python_version < \"3\"".to_owned())),
             ]
-        )
+        );
+        // PEP 440 orders 2.5rc1 before 2.5, so `>=2.5` excludes it
+        let prerelease = semver::Version::parse("2.5.0-rc.1").unwrap();
+        assert!(!version_reqs[1].matches_with_prereleases(&prerelease, true));
+        assert!(version_reqs[1].matches(&semver::Version::parse("2.5.1").unwrap()));
+    }
+
+    #[test]
+    fn parse_metadata_requires_dist_keeps_conditional() {
+        let metadata = "Metadata-Version: 2.1
+Name: requests
+Version: 2.31.0
+Requires-Dist: charset-normalizer (<4,>=2)
+Requires-Dist: idna<4,>=2.5
+Requires-Dist: PySocks!=1.5.7,>=1.5.6; extra == \"socks\"
+
+Requires-Dist: this is the description
+";
+
+        let version_reqs = parse_metadata_requires_dist(metadata).unwrap();
+
+        assert_eq!(version_reqs.len(), 3);
+        assert_eq!(
+            version_reqs[..2],
+            [
+                make_version_req("charset-normalizer", vec!["< 4", ">= 2"]),
+                make_version_req("idna", vec!["< 4", ">= 2.5"]),
+            ]
+        );
+        assert_eq!(version_reqs[2].name(), "PySocks");
+        assert_eq!(version_reqs[2].markers(), Some("extra == \"socks\""));
+    }
+
+    #[test]
+    fn reject_unsupported_release_types() {
+        let limits = ArchiveLimits::default();
+        let results = vec![
+            parse_release_requirements(io::empty(), ReleaseType::BdistEgg, &limits).map(|_| ()),
+            parse_release_entry_points(io::empty(), ReleaseType::BdistWininst, &limits)
+                .map(|_| ()),
+        ];
+        for result in results {
+            match *result.unwrap_err().kind() {
+                ErrorKind::UnsupportedReleaseType(_) => (),
+                ref kind => panic!("unexpected error {:?}", kind),
+            }
+        }
     }
 }
@@ -0,0 +1,152 @@
+use std::io;
+use serde::Serialize;
+use serde_json;
+use serde_json::ser::{CompactFormatter, Formatter, PrettyFormatter, Serializer};
+
+use errors::*;
+
+/// Wraps a formatter so that output matches python's `json.dumps` with the
+/// default `ensure_ascii=True`, escaping every non-ascii character as `\uXXXX`.
+struct EnsureAscii<F>(F);
+
+impl<F: Formatter> Formatter for EnsureAscii<F> {
+    fn write_string_fragment<W: ?Sized>(&mut self, writer: &mut W, fragment: &str) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        if fragment.is_ascii() {
+            return writer.write_all(fragment.as_bytes());
+        }
+        let mut units = [0; 2];
+        for c in fragment.chars() {
+            if c.is_ascii() {
+                write!(writer, "{}", c)?;
+            } else {
+                for unit in c.encode_utf16(&mut units).iter() {
+                    write!(writer, "\\u{:04x}", unit)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn begin_array<W: ?Sized>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.0.begin_array(writer)
+    }
+
+    fn end_array<W: ?Sized>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.0.end_array(writer)
+    }
+
+    fn begin_array_value<W: ?Sized>(&mut self, writer: &mut W, first: bool) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.0.begin_array_value(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.0.end_array_value(writer)
+    }
+
+    fn begin_object<W: ?Sized>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.0.begin_object(writer)
+    }
+
+    fn end_object<W: ?Sized>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.0.end_object(writer)
+    }
+
+    fn begin_object_key<W: ?Sized>(&mut self, writer: &mut W, first: bool) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.0.begin_object_key(writer, first)
+    }
+
+    fn begin_object_value<W: ?Sized>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.0.begin_object_value(writer)
+    }
+
+    fn end_object_value<W: ?Sized>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.0.end_object_value(writer)
+    }
+}
+
+fn to_string_with_formatter<T, F>(value: &T, formatter: F) -> Result<String>
+where
+    T: Serialize,
+    F: Formatter,
+{
+    // Round-tripping through `serde_json::Value` sorts object keys, as with
+    // python's `sort_keys=True`.
+    let value = serde_json::to_value(value)?;
+    let mut bytes = vec![];
+    {
+        let mut serializer = Serializer::with_formatter(&mut bytes, EnsureAscii(formatter));
+        value.serialize(&mut serializer)?;
+    }
+    // Only ascii is ever written, so this can't fail.
+    Ok(String::from_utf8(bytes).unwrap())
+}
+
+/// Equivalent to `json.dumps(value, indent=4, separators=(",", ": "), sort_keys=True)`,
+/// which is how pipenv writes Pipfile.lock.
+pub fn to_string_pretty<T: Serialize>(value: &T) -> Result<String> {
+    to_string_with_formatter(value, PrettyFormatter::with_indent(b"    "))
+}
+
+/// Equivalent to `json.dumps(value, sort_keys=True, separators=(",", ":"))`.
+pub fn to_string_compact<T: Serialize>(value: &T) -> Result<String> {
+    to_string_with_formatter(value, CompactFormatter)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use pipenv_json::*;
+
+    #[test]
+    fn pretty_sorts_keys_indents_and_escapes() {
+        let mut value = HashMap::new();
+        value.insert("b", vec!["x", "caf\u{e9}"]);
+        value.insert("a", vec![]);
+
+        assert_eq!(
+            to_string_pretty(&value).unwrap(),
+            "{\n    \"a\": [],\n    \"b\": [\n        \"x\",\n        \"caf\\u00e9\"\n    ]\n}"
+        );
+    }
+
+    #[test]
+    fn compact_escapes_non_ascii() {
+        let mut value = HashMap::new();
+        value.insert("name", "caf\u{e9} \u{1f40d}");
+
+        assert_eq!(
+            to_string_compact(&value).unwrap(),
+            "{\"name\":\"caf\\u00e9 \\ud83d\\udc0d\"}"
+        );
+    }
+}
@@ -1,55 +1,845 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use serde::de;
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json;
+use sha2::{Digest, Sha256};
+use toml;
 
-type RequiresMap = HashMap<String, String>;
+use pipenv_json;
+use errors::*;
 
-#[derive(Deserialize, Debug)]
+pub type PackageMap = HashMap<String, PackageInfo>;
+
+/// The `pipfile-spec` version written by current pipenv releases.
+pub const PIPFILE_SPEC: u32 = 6;
+
+/// Top level Pipfile tables that aren't package categories.
+const PIPFILE_SETTINGS_TABLES: &[&str] = &["source", "requires", "pipenv", "scripts"];
+
+/// The section of Pipfile.lock a Pipfile category locks into: `packages` and
+/// `dev-packages` are renamed, custom categories such as `docs` are not.
+pub fn lockfile_category(pipfile_category: &str) -> &str {
+    match pipfile_category {
+        "packages" => "default",
+        "dev-packages" => "develop",
+        category => category,
+    }
+}
+
+/// A Pipfile. Every section is optional, as in pipenv.
+#[derive(Debug)]
 pub struct Pipfile {
     pub source: Vec<Source>,
-    pub requires: RequiresMap,
-    pub packages: HashMap<String, PackageInfo>,
-    #[serde(rename = "dev-packages")] pub dev_packages: Option<HashMap<String, PackageInfo>>,
+    pub requires: Requires,
+    /// Every package category, eg. `packages`, `dev-packages` or `docs`.
+    pub categories: BTreeMap<String, PackageMap>,
+    pub pipenv: PipenvSettings,
+    pub scripts: BTreeMap<String, Script>,
 }
+impl<'de> Deserialize<'de> for Pipfile {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Pipfile, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        fn take<T, E>(
+            tables: &mut BTreeMap<String, toml::Value>,
+            key: &str,
+        ) -> ::std::result::Result<T, E>
+        where
+            T: de::DeserializeOwned + Default,
+            E: de::Error,
+        {
+            match tables.remove(key) {
+                Some(value) => value
+                    .try_into()
+                    .map_err(|e| E::custom(format!("invalid [{}]: {}", key, e))),
+                None => Ok(T::default()),
+            }
+        }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Source {
-    url: String,
-    verify_ssl: bool,
+        let mut tables: BTreeMap<String, toml::Value> = Deserialize::deserialize(deserializer)?;
+        let source = take(&mut tables, "source")?;
+        let requires = take(&mut tables, "requires")?;
+        let pipenv = take(&mut tables, "pipenv")?;
+        let scripts = take(&mut tables, "scripts")?;
+        let categories = tables
+            .into_iter()
+            .map(|(category, value)| {
+                parse_packages(&category, value)
+                    .map(|packages| (category, packages))
+                    .map_err(de::Error::custom)
+            })
+            .collect::<::std::result::Result<_, D::Error>>()?;
+        Ok(Pipfile {
+            source: source,
+            requires: requires,
+            categories: categories,
+            pipenv: pipenv,
+            scripts: scripts,
+        })
+    }
+}
+impl Pipfile {
+    /// The packages of `category`, empty if the Pipfile doesn't have it.
+    pub fn category(&self, category: &str) -> &PackageMap {
+        lazy_static! {
+            static ref EMPTY: PackageMap = HashMap::new();
+        }
+        self.categories.get(category).unwrap_or(&EMPTY)
+    }
+    /// The Pipfile's categories, always including `packages` and `dev-packages`.
+    pub fn category_names(&self) -> Vec<String> {
+        let mut names = vec!["packages".to_owned(), "dev-packages".to_owned()];
+        for category in self.categories.keys() {
+            if !names.contains(category) {
+                names.push(category.to_owned());
+            }
+        }
+        names
+    }
+    /// The declared sources, or pipenv's default when there are none.
+    pub fn sources(&self) -> Vec<Source> {
+        if self.source.is_empty() {
+            vec![default_source()]
+        } else {
+            self.source.clone()
+        }
+    }
+    /// The `[scripts]` entry `name`. No command runs scripts yet.
+    #[allow(dead_code)]
+    pub fn script(&self, name: &str) -> Option<&Script> {
+        self.scripts.get(name)
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// The `[requires]` section, which pins the python a project runs on. It's
+/// copied into Pipfile.lock's `_meta.requires`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Requires {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub python_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub python_full_version: Option<String>,
+}
+impl Requires {
+    /// The most specific version required, to look for an interpreter with.
+    pub fn python_request(&self) -> Option<&str> {
+        self.python_full_version
+            .as_ref()
+            .or(self.python_version.as_ref())
+            .map(|x| x.as_str())
+    }
+}
+
+/// The `[pipenv]` section, which tunes how pipenv treats the Pipfile. No
+/// command reads `install_search_all_sources` or `disable_pip_input` yet, as
+/// they only change how pipenv drives pip, but they're kept for those that will.
+#[derive(Deserialize, Debug, Default)]
+#[allow(dead_code)]
+pub struct PipenvSettings {
+    #[serde(default)] pub allow_prereleases: bool,
+    #[serde(default)] pub install_search_all_sources: bool,
+    #[serde(default)] pub sort_pipfile: bool,
+    #[serde(default)] pub disable_pip_input: bool,
+}
+
+/// A `[scripts]` entry: either a shell command line, or a python callable
+/// written as `{call = "package.module:function()"}`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
-pub enum PackageInfo {
-    SimpleString(String),
-    Simple {
+pub enum Script {
+    Command(String),
+    Call { call: String },
+}
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Script::Command(ref command) => write!(f, "{}", command),
+            Script::Call { ref call } => write!(f, "call {}", call),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Source {
+    #[serde(skip_serializing_if = "Option::is_none")] pub name: Option<String>,
+    pub url: String,
+    pub verify_ssl: bool,
+}
+impl Source {
+    /// Whether this is PyPI, the only index packages are resolved against.
+    pub fn is_pypi(&self) -> bool {
+        let host = self.url
+            .splitn(2, "://")
+            .nth(1)
+            .and_then(|x| x.split('/').next());
+        host == Some("pypi.org") || host == Some("pypi.python.org")
+    }
+}
+
+/// The source pipenv assumes when a Pipfile doesn't declare any.
+pub fn default_source() -> Source {
+    Source {
+        name: Some("pypi".to_owned()),
+        url: "https://pypi.org/simple".to_owned(),
+        verify_ssl: true,
+    }
+}
+
+#[derive(Serialize)]
+struct HashedPipfileMeta {
+    requires: serde_json::Value,
+    sources: serde_json::Value,
+}
+
+/// Computes `_meta.hash.sha256` the same way pipenv does: the sha256 of the
+/// Pipfile's sources, requires and package categories, dumped as compact json
+/// with sorted keys. The raw TOML is used rather than `Pipfile` so that
+/// anything pipenv would hash but we don't model still counts.
+pub fn pipfile_hash(pipfile_bytes: &[u8]) -> Result<String> {
+    let mut pipfile: BTreeMap<String, serde_json::Value> =
+        toml::from_slice(pipfile_bytes).chain_err(|| "failed to parse Pipfile")?;
+    let sources = match pipfile.remove("source") {
+        Some(sources) => sources,
+        None => serde_json::to_value(vec![default_source()])?,
+    };
+    let mut section = |name: &str| {
+        pipfile
+            .remove(name)
+            .unwrap_or_else(|| serde_json::Value::Object(serde_json::Map::new()))
+    };
+    let mut hashed = BTreeMap::new();
+    hashed.insert(
+        "_meta".to_owned(),
+        serde_json::to_value(HashedPipfileMeta {
+            requires: section("requires"),
+            sources: sources,
+        })?,
+    );
+    hashed.insert("default".to_owned(), section("packages"));
+    hashed.insert("develop".to_owned(), section("dev-packages"));
+    for (category, packages) in pipfile.into_iter() {
+        if !PIPFILE_SETTINGS_TABLES.contains(&category.as_str()) {
+            hashed.insert(category, packages);
+        }
+    }
+    let json = pipenv_json::to_string_compact(&hashed)?;
+    Ok(format!("{:x}", Sha256::digest(json.as_bytes())))
+}
+
+/// Where a Pipfile entry's distribution comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PackageSource {
+    /// Resolved from a package index, optionally a named `[[source]]`.
+    Index {
         version: String,
-        hash: Option<String>,
+        index: Option<String>,
     },
+    /// A local project directory or archive.
+    Path { path: String, editable: bool },
+    /// An archive at a url.
+    File { file: String },
     Git {
         git: String,
-        #[serde(rename = "ref")] ref_: String,
-        #[serde(default = "git_editable_default")] editable: bool,
+        ref_: Option<String>,
+        subdirectory: Option<String>,
+        editable: bool,
     },
 }
 
-fn git_editable_default() -> bool {
-    false
+/// A single entry of `[packages]` or `[dev-packages]`, either a bare version
+/// specifier such as `"*"` or a table such as `{version = ">=2", extras = ["socks"]}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageInfo {
+    pub source: PackageSource,
+    pub extras: Vec<String>,
+    /// PEP 508 markers, combining `markers` with keys such as `sys_platform`.
+    pub markers: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Keys that pipenv turns into a marker, eg. `sys_platform = "== 'win32'"`.
+const MARKER_KEYS: &[&str] = &[
+    "os_name",
+    "sys_platform",
+    "platform_machine",
+    "platform_python_implementation",
+    "platform_release",
+    "platform_system",
+    "platform_version",
+    "python_version",
+    "python_full_version",
+    "implementation_name",
+    "implementation_version",
+];
+
+impl PackageInfo {
+    /// Validates the entry for package `name`, naming it in any error.
+    pub fn from_toml(name: &str, value: &toml::Value) -> Result<PackageInfo> {
+        let invalid = |reason: String| ErrorKind::InvalidPackageEntry(name.to_owned(), reason);
+        let table = match *value {
+            toml::Value::String(ref version) => {
+                return Ok(PackageInfo {
+                    source: PackageSource::Index {
+                        version: version.to_owned(),
+                        index: None,
+                    },
+                    extras: vec![],
+                    markers: None,
+                })
+            }
+            toml::Value::Table(ref table) => table,
+            _ => bail!(invalid("expected a version string or a table".to_owned())),
+        };
+        let string = |key: &str| -> Result<Option<String>> {
+            match table.get(key) {
+                None => Ok(None),
+                Some(&toml::Value::String(ref value)) => Ok(Some(value.to_owned())),
+                Some(_) => bail!(invalid(format!("`{}` must be a string", key))),
+            }
+        };
+        let editable = match table.get("editable") {
+            None => false,
+            Some(&toml::Value::Boolean(editable)) => editable,
+            Some(_) => bail!(invalid("`editable` must be a boolean".to_owned())),
+        };
+        let extras = match table.get("extras") {
+            None => vec![],
+            Some(&toml::Value::Array(ref extras)) => extras
+                .iter()
+                .map(|extra| {
+                    extra
+                        .as_str()
+                        .map(|x| x.to_owned())
+                        .ok_or_else(|| invalid("`extras` must be strings".to_owned()).into())
+                })
+                .collect::<Result<Vec<String>>>()?,
+            Some(_) => bail!(invalid("`extras` must be an array".to_owned())),
+        };
+
+        let mut markers = vec![];
+        if let Some(entry_markers) = string("markers")? {
+            markers.push(entry_markers);
+        }
+        for key in table.keys() {
+            let known = [
+                "version", "hash", "extras", "markers", "index", "editable", "path", "file",
+                "git", "ref", "subdirectory",
+            ];
+            if MARKER_KEYS.contains(&key.as_str()) {
+                markers.push(format!("{} {}", key, string(key)?.unwrap()));
+            } else if !known.contains(&key.as_str()) {
+                bail!(invalid(format!("unknown key `{}`", key)));
+            }
+        }
+        let markers = match markers.len() {
+            0 => None,
+            1 => Some(markers.remove(0)),
+            _ => Some(
+                markers
+                    .iter()
+                    .map(|x| format!("({})", x))
+                    .collect::<Vec<String>>()
+                    .join(" and "),
+            ),
+        };
+
+        let kinds: Vec<&str> = ["path", "file", "git"]
+            .iter()
+            .cloned()
+            .filter(|key| table.contains_key(*key))
+            .collect();
+        if kinds.len() > 1 {
+            bail!(invalid(format!("`{}` can't be combined", kinds.join("` and `"))));
+        }
+        let only_with = |key: &str, allowed: &[&str]| -> Result<()> {
+            if !table.contains_key(key) || allowed.iter().any(|x| kinds.contains(x)) {
+                return Ok(());
+            }
+            match kinds.first() {
+                Some(kind) => bail!(invalid(format!("`{}` can't be used with `{}`", key, kind))),
+                None => bail!(invalid(format!("`{}` needs `{}`", key, allowed.join("` or `")))),
+            }
+        };
+        only_with("ref", &["git"])?;
+        only_with("subdirectory", &["git"])?;
+        only_with("editable", &["path", "git"])?;
+        if let Some(kind) = kinds.first() {
+            for key in ["version", "index"].iter() {
+                if table.contains_key(*key) {
+                    bail!(invalid(format!("`{}` can't be used with `{}`", key, kind)));
+                }
+            }
+        }
+
+        let source = match kinds.first() {
+            Some(&"path") => PackageSource::Path {
+                path: string("path")?.unwrap(),
+                editable: editable,
+            },
+            Some(&"file") => PackageSource::File {
+                file: string("file")?.unwrap(),
+            },
+            Some(_) => PackageSource::Git {
+                git: string("git")?.unwrap(),
+                ref_: string("ref")?,
+                subdirectory: string("subdirectory")?,
+                editable: editable,
+            },
+            None => PackageSource::Index {
+                version: string("version")?.unwrap_or_else(|| "*".to_owned()),
+                index: string("index")?,
+            },
+        };
+        Ok(PackageInfo {
+            source: source,
+            extras: extras,
+            markers: markers,
+        })
+    }
+
+    /// The PEP 440 specifier for packages resolved from an index, `None` otherwise.
+    pub fn version_spec(&self) -> Option<&str> {
+        match self.source {
+            PackageSource::Index { ref version, .. } => Some(version),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a package category, validating each entry with
+/// `PackageInfo::from_toml` so that errors name the offending package.
+fn parse_packages(category: &str, value: toml::Value) -> Result<PackageMap> {
+    let raw = match value {
+        toml::Value::Table(raw) => raw,
+        _ => bail!("invalid [{}]: expected a table of packages", category),
+    };
+    raw.iter()
+        .map(|(name, value)| {
+            PackageInfo::from_toml(name, value).map(|package_info| (name.to_owned(), package_info))
+        })
+        .collect()
+}
+
+pub type LockedCategory = BTreeMap<String, LockedPackage>;
+
+#[derive(Debug)]
 pub struct Lockfile {
-    default: HashMap<String, PackageInfo>,
-    develop: HashMap<String, PackageInfo>,
-    _meta: LockfileMeta,
+    /// Locked packages keyed by lockfile section, eg. `default`, `develop` or `docs`.
+    pub categories: BTreeMap<String, LockedCategory>,
+    pub meta: LockfileMeta,
+}
+impl Serialize for Lockfile {
+    fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.categories.len() + 1))?;
+        map.serialize_entry("_meta", &self.meta)?;
+        for (category, packages) in self.categories.iter() {
+            map.serialize_entry(category, packages)?;
+        }
+        map.end()
+    }
+}
+impl<'de> Deserialize<'de> for Lockfile {
+    fn deserialize<D>(deserializer: D) -> ::std::result::Result<Lockfile, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut sections: BTreeMap<String, serde_json::Value> =
+            Deserialize::deserialize(deserializer)?;
+        let meta = sections
+            .remove("_meta")
+            .ok_or_else(|| de::Error::missing_field("_meta"))?;
+        Ok(Lockfile {
+            meta: serde_json::from_value(meta).map_err(de::Error::custom)?,
+            categories: sections
+                .into_iter()
+                .map(|(category, packages)| {
+                    serde_json::from_value(packages)
+                        .map(|packages| (category, packages))
+                        .map_err(de::Error::custom)
+                })
+                .collect::<::std::result::Result<_, D::Error>>()?,
+        })
+    }
+}
+impl Lockfile {
+    /// The locked packages of `category`, a lockfile section name.
+    pub fn category(&self, category: &str) -> Option<&LockedCategory> {
+        self.categories.get(category)
+    }
+
+    /// Whether this lock was generated from a Pipfile with exactly these contents.
+    pub fn matches_pipfile(&self, pipfile_bytes: &[u8]) -> Result<bool> {
+        Ok(self.meta.hash.sha256 == pipfile_hash(pipfile_bytes)?)
+    }
+
+    /// Serializes exactly as pipenv does, so that relocking an unchanged
+    /// project produces no diff.
+    pub fn to_json_string(&self) -> Result<String> {
+        let mut json = pipenv_json::to_string_pretty(self)?;
+        json.push('\n');
+        Ok(json)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct LockfileMeta {
-    hash: LockfileMetaHash,
-    requires: RequiresMap,
-    sources: Vec<Source>,
+pub struct LockfileMeta {
+    pub hash: LockfileMetaHash,
+    #[serde(rename = "pipfile-spec", skip_serializing_if = "Option::is_none")]
+    pub pipfile_spec: Option<u32>,
+    pub requires: Requires,
+    pub sources: Vec<Source>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct LockfileMetaHash {
-    sha256: String,
+pub struct LockfileMetaHash {
+    pub sha256: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct LockedPackage {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")] pub hashes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub markers: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")] pub extras: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub index: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub git: Option<String>,
+    #[serde(rename = "ref", skip_serializing_if = "Option::is_none")]
+    pub ref_: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub subdirectory: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] pub editable: Option<bool>,
+}
+
+impl LockedPackage {
+    /// This package as a requirements.txt line, eg. `requests[socks]==2.18.4`.
+    pub fn to_requirement(&self, name: &str, with_hashes: bool) -> String {
+        let extras = if self.extras.is_empty() {
+            "".to_owned()
+        } else {
+            format!("[{}]", self.extras.join(","))
+        };
+        let mut requirement = match (&self.git, &self.path, &self.file) {
+            (&Some(ref git), _, _) => {
+                let mut url = format!("git+{}", git);
+                if let Some(ref ref_) = self.ref_ {
+                    url.push_str(&format!("@{}", ref_));
+                }
+                url.push_str(&format!("#egg={}", name));
+                if let Some(ref subdirectory) = self.subdirectory {
+                    url.push_str(&format!("&subdirectory={}", subdirectory));
+                }
+                url
+            }
+            (_, &Some(ref path), _) => format!("{}{}", path, extras),
+            (_, _, &Some(ref file)) => file.to_owned(),
+            _ => format!("{}{}{}", name, extras, self.version.as_ref().map_or("", |x| x)),
+        };
+        if self.editable == Some(true) {
+            requirement = format!("-e {}", requirement);
+        }
+        if let Some(ref markers) = self.markers {
+            requirement.push_str(&format!("; {}", markers));
+        }
+        if with_hashes {
+            for hash in self.hashes.iter() {
+                requirement.push_str(&format!(" --hash={}", hash));
+            }
+        }
+        requirement
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use toml;
+    use pipfile::*;
+
+    const PIPFILE: &str = r#"[[source]]
+url = "https://pypi.python.org/simple"
+verify_ssl = true
+name = "pypi"
+
+[packages]
+requests = "*"
+django = {version = ">=1.11", extras = ["bcrypt"]}
+
+[dev-packages]
+pytest = "==3.2.3"
+
+[requires]
+python_version = "3.6"
+"#;
+
+    #[test]
+    fn parse_package_entries() {
+        let pipfile: Pipfile = toml::from_str(
+            r#"
+[packages]
+requests = {version = "*", extras = ["socks"], sys_platform = "== 'linux'"}
+project = {path = ".", editable = true}
+archive = {file = "https://example.com/archive-1.0.tar.gz"}
+lib = {git = "https://github.com/example/lib.git", ref = "main", subdirectory = "pkg"}
+pywin32 = {version = ">=220", markers = "os_name == 'nt'", index = "pypi"}
+"#,
+        ).unwrap();
+        let package = |name: &str| pipfile.category("packages")[name].clone();
+
+        assert_eq!(
+            package("requests"),
+            PackageInfo {
+                source: PackageSource::Index {
+                    version: "*".to_owned(),
+                    index: None,
+                },
+                extras: vec!["socks".to_owned()],
+                markers: Some("sys_platform == 'linux'".to_owned()),
+            }
+        );
+        assert_eq!(
+            package("project").source,
+            PackageSource::Path {
+                path: ".".to_owned(),
+                editable: true,
+            }
+        );
+        assert_eq!(package("archive").version_spec(), None);
+        assert_eq!(
+            package("lib").source,
+            PackageSource::Git {
+                git: "https://github.com/example/lib.git".to_owned(),
+                ref_: Some("main".to_owned()),
+                subdirectory: Some("pkg".to_owned()),
+                editable: false,
+            }
+        );
+        assert_eq!(package("pywin32").version_spec(), Some(">=220"));
+        assert_eq!(package("pywin32").markers, Some("os_name == 'nt'".to_owned()));
+    }
+
+    #[test]
+    fn optional_sections_and_settings() {
+        let pipfile: Pipfile = toml::from_str(
+            r#"
+[pipenv]
+allow_prereleases = true
+sort_pipfile = true
+disable_pip_input = true
+
+[scripts]
+tests = "pytest -x"
+serve = {call = "app.server:main()"}
+"#,
+        ).unwrap();
+
+        assert_eq!(pipfile.sources()[0].url, "https://pypi.org/simple");
+        assert!(pipfile.category("packages").is_empty());
+        assert_eq!(pipfile.requires, Requires::default());
+        assert!(pipfile.pipenv.allow_prereleases);
+        assert!(pipfile.pipenv.sort_pipfile);
+        assert!(pipfile.pipenv.disable_pip_input);
+        assert!(!pipfile.pipenv.install_search_all_sources);
+        assert_eq!(
+            pipfile.script("tests"),
+            Some(&Script::Command("pytest -x".to_owned()))
+        );
+        assert_eq!(pipfile.script("serve").unwrap().to_string(), "call app.server:main()");
+    }
+
+    #[test]
+    fn invalid_package_entries_name_the_package() {
+        let invalid = [
+            r#"broken = {path = ".", git = "https://github.com/example/lib.git"}"#,
+            r#"broken = {version = "*", ref = "main"}"#,
+            r#"broken = {file = "broken.zip", editable = true}"#,
+            r#"broken = {version = "*", colour = "red"}"#,
+            r#"broken = 3"#,
+        ];
+        for entry in invalid.iter() {
+            let pipfile = format!("[packages]\n{}\n", entry);
+            let err = toml::from_str::<Pipfile>(&pipfile).unwrap_err();
+
+            assert!(err.to_string().contains("broken"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn custom_categories() {
+        let pipfile: Pipfile = toml::from_str(
+            r#"
+[packages]
+requests = "*"
+
+[docs]
+sphinx = ">=1.6"
+
+[scripts]
+docs = "sphinx-build docs build"
+"#,
+        ).unwrap();
+
+        assert_eq!(
+            pipfile.category_names(),
+            vec!["packages", "dev-packages", "docs"]
+        );
+        assert_eq!(pipfile.category("docs")["sphinx"].version_spec(), Some(">=1.6"));
+        assert!(pipfile.category("dev-packages").is_empty());
+        assert_eq!(lockfile_category("dev-packages"), "develop");
+        assert_eq!(lockfile_category("docs"), "docs");
+    }
+
+    #[test]
+    fn pipfile_hash_includes_custom_categories() {
+        let with_docs = format!("{}\n[docs]\nsphinx = \"*\"\n", PIPFILE);
+
+        assert_ne!(
+            pipfile_hash(with_docs.as_bytes()).unwrap(),
+            pipfile_hash(PIPFILE.as_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn requirement_lines() {
+        let package = LockedPackage {
+            hashes: vec!["sha256:abc".to_owned()],
+            version: Some("==2.18.4".to_owned()),
+            markers: Some("python_version >= '3'".to_owned()),
+            extras: vec!["socks".to_owned()],
+            ..Default::default()
+        };
+        let git = LockedPackage {
+            git: Some("https://github.com/example/lib.git".to_owned()),
+            ref_: Some("main".to_owned()),
+            editable: Some(true),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            package.to_requirement("requests", true),
+            "requests[socks]==2.18.4; python_version >= '3' --hash=sha256:abc"
+        );
+        assert_eq!(
+            git.to_requirement("lib", false),
+            "-e git+https://github.com/example/lib.git@main#egg=lib"
+        );
+    }
+
+    #[test]
+    fn pipfile_hash_matches_pipenv() {
+        // Expected value computed in python with pipenv's `Pipfile.hash` algorithm
+        assert_eq!(
+            pipfile_hash(PIPFILE.as_bytes()).unwrap(),
+            "6a99afb7089e746eb03dc8da8df0c3976ec3d0b9b4ffe8956e5cf5fa5e6823cc"
+        );
+    }
+
+    #[test]
+    fn pipfile_hash_ignores_formatting() {
+        let reformatted = PIPFILE
+            .replace("requests = \"*\"", "\"requests\" = '*' # comment")
+            .replace("[dev-packages]", "\n[dev-packages]");
+
+        assert_eq!(
+            pipfile_hash(reformatted.as_bytes()).unwrap(),
+            pipfile_hash(PIPFILE.as_bytes()).unwrap()
+        );
+        assert!(
+            pipfile_hash(PIPFILE.replace("3.6", "3.7").as_bytes()).unwrap()
+                != pipfile_hash(PIPFILE.as_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn lockfile_matches_pipenv_formatting() {
+        let mut default = BTreeMap::new();
+        default.insert(
+            "requests".to_owned(),
+            LockedPackage {
+                hashes: vec!["sha256:abc".to_owned()],
+                version: Some("==2.18.4".to_owned()),
+                index: Some("pypi".to_owned()),
+                ..Default::default()
+            },
+        );
+        let requires = Requires {
+            python_version: Some("3.6".to_owned()),
+            ..Default::default()
+        };
+        let mut categories = BTreeMap::new();
+        categories.insert("default".to_owned(), default);
+        categories.insert("develop".to_owned(), BTreeMap::new());
+        let lockfile = Lockfile {
+            categories: categories,
+            meta: LockfileMeta {
+                hash: LockfileMetaHash {
+                    sha256: "123".to_owned(),
+                },
+                pipfile_spec: Some(PIPFILE_SPEC),
+                requires: requires,
+                sources: vec![
+                    Source {
+                        name: Some("pypi".to_owned()),
+                        url: "https://pypi.python.org/simple".to_owned(),
+                        verify_ssl: true,
+                    },
+                ],
+            },
+        };
+
+        assert_eq!(
+            lockfile.to_json_string().unwrap(),
+            r#"{
+    "_meta": {
+        "hash": {
+            "sha256": "123"
+        },
+        "pipfile-spec": 6,
+        "requires": {
+            "python_version": "3.6"
+        },
+        "sources": [
+            {
+                "name": "pypi",
+                "url": "https://pypi.python.org/simple",
+                "verify_ssl": true
+            }
+        ]
+    },
+    "default": {
+        "requests": {
+            "hashes": [
+                "sha256:abc"
+            ],
+            "index": "pypi",
+            "version": "==2.18.4"
+        }
+    },
+    "develop": {}
+}
+"#
+        );
+        let parsed: Lockfile = serde_json::from_str(&lockfile.to_json_string().unwrap()).unwrap();
+        assert_eq!(parsed.category("default"), lockfile.category("default"));
+        assert_eq!(parsed.categories.len(), 2);
+    }
+
+    #[test]
+    fn only_pypi_urls_are_pypi() {
+        let source = |url: &str| Source {
+            name: None,
+            url: url.to_owned(),
+            verify_ssl: true,
+        };
+
+        assert!(default_source().is_pypi());
+        assert!(source("https://pypi.python.org/simple").is_pypi());
+        assert!(!source("https://pypi.example.com/simple").is_pypi());
+        assert!(!source("https://example.com/pypi.org/simple").is_pypi());
+    }
 }
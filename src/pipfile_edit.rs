@@ -0,0 +1,359 @@
+use std::fmt;
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, InlineTable, Item, Table, Value};
+
+use pipfile::{PackageInfo, PackageSource, Source};
+use pypi::canonical_name;
+use errors::*;
+
+/// A Pipfile opened for editing. Edits are made to a format preserving TOML
+/// document, so comments, ordering and whitespace survive them, and a Pipfile
+/// that wasn't changed is written back byte for byte.
+pub struct PipfileEditor {
+    document: DocumentMut,
+}
+impl PipfileEditor {
+    pub fn parse(contents: &str) -> Result<PipfileEditor> {
+        Ok(PipfileEditor {
+            document: contents
+                .parse::<DocumentMut>()
+                .chain_err(|| "failed to parse Pipfile")?,
+        })
+    }
+
+    /// The key `name` is written under in `category`, matching names the way
+    /// PEP 503 does so that eg. `Django` finds an existing `django` entry.
+    fn package_key(&self, category: &str, name: &str) -> Option<String> {
+        let table = self.document.get(category)?.as_table_like()?;
+        let canonical = canonical_name(name);
+        table
+            .iter()
+            .map(|(key, _)| key)
+            .find(|key| canonical_name(key) == canonical)
+            .map(|key| key.to_owned())
+    }
+
+    /// Adds `name` to `category`, creating the category if needed, or
+    /// replaces its existing entry in place.
+    pub fn set_package(
+        &mut self,
+        category: &str,
+        name: &str,
+        package_info: &PackageInfo,
+    ) -> Result<()> {
+        let key = self.package_key(category, name)
+            .unwrap_or_else(|| name.to_owned());
+        let table = self.document
+            .entry(category)
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_like_mut()
+            .ok_or_else(|| format!("[{}] isn't a table in Pipfile", category))?;
+        let mut new_value = package_value(package_info);
+        match table.get_mut(&key) {
+            Some(item) => {
+                // Keep the spacing and any trailing comment of the entry being replaced
+                if let Some(old_value) = item.as_value() {
+                    *new_value.decor_mut() = old_value.decor().clone();
+                }
+                *item = Item::Value(new_value);
+            }
+            None => {
+                table.insert(&key, Item::Value(new_value));
+            }
+        }
+        Ok(())
+    }
+
+    /// Changes the version of `name`'s existing table entry in `category` to
+    /// that of `package_info`, along with its extras and markers when it has
+    /// any, keeping whatever else the entry sets, such as its index. Returns
+    /// whether there was such an entry: bare version strings and entries
+    /// that don't install from an index are left for `set_package`.
+    pub fn update_package(
+        &mut self,
+        category: &str,
+        name: &str,
+        package_info: &PackageInfo,
+    ) -> Result<bool> {
+        let version = match package_info.source {
+            PackageSource::Index { ref version, .. } => version,
+            _ => return Ok(false),
+        };
+        let key = match self.package_key(category, name) {
+            Some(key) => key,
+            None => return Ok(false),
+        };
+        let table = match self.document
+            .get_mut(category)
+            .and_then(|item| item.as_table_like_mut())
+            .and_then(|table| table.get_mut(&key))
+            .and_then(|item| item.as_table_like_mut())
+        {
+            Some(ref table) if ["git", "path", "file"].iter().any(|x| table.contains_key(x)) => {
+                return Ok(false);
+            }
+            Some(table) => table,
+            None => return Ok(false),
+        };
+        table.insert("version", value(version.as_str()));
+        if !package_info.extras.is_empty() {
+            let extras: Array = package_info.extras.iter().map(|x| x.as_str()).collect();
+            table.insert("extras", value(extras));
+        }
+        if let Some(ref markers) = package_info.markers {
+            table.insert("markers", value(markers.as_str()));
+        }
+        Ok(true)
+    }
+
+    /// Removes `name` from `category`, returning whether it was there.
+    pub fn remove_package(&mut self, category: &str, name: &str) -> bool {
+        let key = match self.package_key(category, name) {
+            Some(key) => key,
+            None => return false,
+        };
+        self.document
+            .get_mut(category)
+            .and_then(|item| item.as_table_like_mut())
+            .and_then(|table| table.remove(&key))
+            .is_some()
+    }
+
+    /// Sorts the entries of `category` by name, as pipenv's `sort_pipfile` does.
+    pub fn sort_category(&mut self, category: &str) {
+        if let Some(table) = self.document
+            .get_mut(category)
+            .and_then(|item| item.as_table_like_mut())
+        {
+            table.sort_values();
+        }
+    }
+
+    /// Requires `python_version` in `[requires]`, along with
+    /// `python_full_version` when given. An existing `python_full_version`
+    /// is removed otherwise, since it would contradict the new version.
+    pub fn set_python_requirement(
+        &mut self,
+        python_version: &str,
+        python_full_version: Option<&str>,
+    ) -> Result<()> {
+        let requires = self.document
+            .entry("requires")
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_like_mut()
+            .ok_or("[requires] isn't a table in Pipfile")?;
+        requires.insert("python_version", value(python_version));
+        match python_full_version {
+            Some(version) => {
+                requires.insert("python_full_version", value(version));
+            }
+            None => {
+                requires.remove("python_full_version");
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends a `[[source]]`, unless one with the same url already exists.
+    /// Returns whether the source was added.
+    pub fn add_source(&mut self, source: &Source) -> Result<bool> {
+        let sources = self.document
+            .entry("source")
+            .or_insert_with(|| Item::ArrayOfTables(ArrayOfTables::new()))
+            .as_array_of_tables_mut()
+            .ok_or("`source` isn't an array of tables in Pipfile")?;
+        if sources
+            .iter()
+            .any(|table| table.get("url").and_then(|x| x.as_str()) == Some(&source.url))
+        {
+            return Ok(false);
+        }
+        let mut table = Table::new();
+        if let Some(ref name) = source.name {
+            table.insert("name", value(name.as_str()));
+        }
+        table.insert("url", value(source.url.as_str()));
+        table.insert("verify_ssl", value(source.verify_ssl));
+        sources.push(table);
+        Ok(true)
+    }
+}
+impl fmt::Display for PipfileEditor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.document)
+    }
+}
+
+/// The TOML pipenv would write for an entry: a bare version string when
+/// nothing else is set, otherwise an inline table.
+fn package_value(package_info: &PackageInfo) -> Value {
+    let mut table = InlineTable::new();
+    match package_info.source {
+        PackageSource::Index {
+            ref version,
+            ref index,
+        } => {
+            if package_info.extras.is_empty() && package_info.markers.is_none()
+                && index.is_none()
+            {
+                return Value::from(version.as_str());
+            }
+            table.insert("version", Value::from(version.as_str()));
+            if let Some(ref index) = *index {
+                table.insert("index", Value::from(index.as_str()));
+            }
+        }
+        PackageSource::Path { ref path, editable } => {
+            table.insert("path", Value::from(path.as_str()));
+            if editable {
+                table.insert("editable", Value::from(true));
+            }
+        }
+        PackageSource::File { ref file } => {
+            table.insert("file", Value::from(file.as_str()));
+        }
+        PackageSource::Git {
+            ref git,
+            ref ref_,
+            ref subdirectory,
+            editable,
+        } => {
+            table.insert("git", Value::from(git.as_str()));
+            if let Some(ref ref_) = *ref_ {
+                table.insert("ref", Value::from(ref_.as_str()));
+            }
+            if let Some(ref subdirectory) = *subdirectory {
+                table.insert("subdirectory", Value::from(subdirectory.as_str()));
+            }
+            if editable {
+                table.insert("editable", Value::from(true));
+            }
+        }
+    }
+    if !package_info.extras.is_empty() {
+        let extras: Array = package_info.extras.iter().map(|x| x.as_str()).collect();
+        table.insert("extras", Value::Array(extras));
+    }
+    if let Some(ref markers) = package_info.markers {
+        table.insert("markers", Value::from(markers.as_str()));
+    }
+    Value::InlineTable(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use toml;
+    use pipfile::*;
+    use pipfile_edit::*;
+
+    const PIPFILE: &str = r#"# The project's dependencies
+[[source]]
+url = "https://pypi.org/simple"
+verify_ssl = true
+name = "pypi"
+
+[packages]
+Django = "*"   # keep up to date
+requests = {version = ">=2.18", extras = ["socks"]}
+
+[dev-packages]
+pytest = "==3.2.3"
+"#;
+
+    fn package(entry: &str) -> PackageInfo {
+        let value: toml::Value = toml::from_str(&format!("entry = {}", entry)).unwrap();
+        PackageInfo::from_toml("entry", &value["entry"]).unwrap()
+    }
+
+    #[test]
+    fn unchanged_round_trip_is_identical() {
+        assert_eq!(PipfileEditor::parse(PIPFILE).unwrap().to_string(), PIPFILE);
+    }
+
+    #[test]
+    fn update_in_place_keeping_comments() {
+        let mut editor = PipfileEditor::parse(PIPFILE).unwrap();
+
+        editor.set_package("packages", "django", &package(r#""~=1.11""#)).unwrap();
+        editor.set_package("packages", "six", &package(r#""*""#)).unwrap();
+
+        assert_eq!(
+            editor.to_string(),
+            PIPFILE
+                .replace(r#"Django = "*""#, r#"Django = "~=1.11""#)
+                .replace("\n\n[dev-packages]", "\nsix = \"*\"\n\n[dev-packages]")
+        );
+    }
+
+    #[test]
+    fn update_only_the_version_of_a_table_entry() {
+        let mut editor = PipfileEditor::parse(PIPFILE).unwrap();
+        editor
+            .set_package(
+                "packages",
+                "requests",
+                &package(r#"{version = ">=2.18", extras = ["socks"], index = "internal"}"#),
+            )
+            .unwrap();
+
+        assert!(editor.update_package("packages", "Requests", &package(r#""~=2.31""#)).unwrap());
+        assert!(!editor.update_package("packages", "django", &package(r#""~=1.11""#)).unwrap());
+        assert!(!editor.update_package("packages", "six", &package(r#""*""#)).unwrap());
+
+        let pipfile: Pipfile = toml::from_str(&editor.to_string()).unwrap();
+        assert_eq!(
+            pipfile.category("packages")["requests"],
+            package(r#"{version = "~=2.31", extras = ["socks"], index = "internal"}"#)
+        );
+    }
+
+    #[test]
+    fn add_to_new_category_and_remove() {
+        let mut editor = PipfileEditor::parse(PIPFILE).unwrap();
+
+        editor
+            .set_package(
+                "docs",
+                "sphinx",
+                &package(r#"{version = ">=1.6", markers = "python_version >= '3'"}"#),
+            )
+            .unwrap();
+        assert!(editor.remove_package("dev-packages", "PyTest"));
+        assert!(!editor.remove_package("dev-packages", "pytest"));
+
+        let pipfile: Pipfile = toml::from_str(&editor.to_string()).unwrap();
+        assert!(pipfile.category("dev-packages").is_empty());
+        assert_eq!(
+            pipfile.category("docs")["sphinx"],
+            package(r#"{version = ">=1.6", markers = "python_version >= '3'"}"#)
+        );
+    }
+
+    #[test]
+    fn set_python_requirement() {
+        let mut editor = PipfileEditor::parse(PIPFILE).unwrap();
+
+        editor.set_python_requirement("3.6", Some("3.6.3")).unwrap();
+        editor.set_python_requirement("3.11", None).unwrap();
+
+        let pipfile: Pipfile = toml::from_str(&editor.to_string()).unwrap();
+        assert_eq!(pipfile.requires.python_version, Some("3.11".to_owned()));
+        assert_eq!(pipfile.requires.python_full_version, None);
+    }
+
+    #[test]
+    fn add_source_once() {
+        let mut editor = PipfileEditor::parse(PIPFILE).unwrap();
+        let source = Source {
+            name: Some("internal".to_owned()),
+            url: "https://pypi.example.com/simple".to_owned(),
+            verify_ssl: true,
+        };
+
+        assert!(editor.add_source(&source).unwrap());
+        assert!(!editor.add_source(&source).unwrap());
+
+        let pipfile: Pipfile = toml::from_str(&editor.to_string()).unwrap();
+        assert_eq!(pipfile.source.len(), 2);
+        assert_eq!(pipfile.source[1].name, Some("internal".to_owned()));
+    }
+}
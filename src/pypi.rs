@@ -1,15 +1,60 @@
 use std::collections::HashMap;
+use std::io::{self, Write};
 use std::str;
+use regex::Regex;
 use semver;
 use reqwest;
 
-use parse_release::parse_release_requirements;
-use semver_utils::normalize_and_parse_version_string;
+use hashing::{copy_verified, read_verified};
+use archive::ArchiveLimits;
+use entry_points::EntryPoints;
+use parse_release::{parse_release_entry_points, parse_release_requirements};
+use semver_utils::{is_prerelease, normalize_and_parse_version_string};
 use version_req::PackageVersionReq;
 
 use release::ReleaseType;
+use tags::TargetPython;
+use wheel::WheelFilename;
 use errors::*;
 
+pub fn get_package_data(client: &reqwest::Client, package_name: &str) -> Result<PypiPackage> {
+    let mut resp = client
+        .get(&format!(
+            "https://pypi.python.org/pypi/{}/json",
+            package_name
+        ))
+        .send()?;
+    Ok(resp.json()?)
+}
+
+/// Whether `target` satisfies a `Requires-Python` specifier. A target without
+/// a micro version satisfies it if any of its micro versions would. Like pip,
+/// metadata we can't parse doesn't exclude anything.
+fn python_satisfies(target: &TargetPython, requires_python: &str) -> bool {
+    PackageVersionReq::from_specifier("python", requires_python)
+        .map(|req| {
+            target
+                .python_versions(requires_python)
+                .iter()
+                .any(|version| req.matches(version))
+        })
+        .unwrap_or(true)
+}
+
+/// Whether a version's `files` have all been yanked, which yanks the version.
+fn all_yanked(files: &[ReleaseMetadata]) -> bool {
+    !files.is_empty() && files.iter().all(|release| release.yanked)
+}
+
+/// Normalizes a project name as described in PEP 503, eg. `Zope_Interface` ->
+/// `zope-interface`.
+pub fn canonical_name(name: &str) -> String {
+    lazy_static! {
+        static ref SEPARATORS_RE: Regex = Regex::new(r"[-_.]+").unwrap();
+    }
+    SEPARATORS_RE.replace_all(name, "-").to_lowercase()
+}
+
 #[derive(Deserialize, Debug)]
 pub struct PypiPackage {
     info: PackageInfo,
@@ -17,37 +62,92 @@ pub struct PypiPackage {
     urls: Vec<ReleaseMetadata>,
 }
 impl PypiPackage {
-    fn get_requires_for_version_and_release_type(
+    /// The files of `version` whose `Requires-Python` admits `target`. Files
+    /// without their own `requires_python` inherit the project's, which PyPI
+    /// only reports for the latest release. Yanked files are left out unless
+    /// `allow_yanked`.
+    fn files_for_python<'a>(
         &self,
-        client: &reqwest::Client,
         version: &semver::Version,
-        release_type: ReleaseType,
-    ) -> Result<Vec<PackageVersionReq>> {
-        let release = self.releases()?
-            .get(version)
-            .ok_or_else(|| {
-                ErrorKind::VersionDoesntExist(self.info.name.to_owned(), version.clone())
-            })?
+        files: &'a [ReleaseMetadata],
+        target: &TargetPython,
+        allow_yanked: bool,
+    ) -> Vec<&'a ReleaseMetadata> {
+        let is_latest = normalize_and_parse_version_string(&self.info.version).ok().as_ref()
+            == Some(version);
+        files
+            .iter()
+            .filter(|release| allow_yanked || !release.yanked)
+            .filter(|release| {
+                let requires_python = match release.requires_python {
+                    Some(ref requires_python) => Some(requires_python),
+                    None if is_latest => self.info.requires_python.as_ref(),
+                    None => None,
+                };
+                requires_python.map_or(true, |requires_python| {
+                    python_satisfies(target, requires_python)
+                })
+            })
+            .collect()
+    }
+
+    /// The file whose metadata best reflects what would be installed on
+    /// `target`: its most preferred compatible wheel, falling back to the sdist.
+    /// Yanked files are only used when every file of `version` was yanked.
+    pub fn best_file_for_version(
+        &self,
+        version: &semver::Version,
+        target: &TargetPython,
+    ) -> Result<&ReleaseMetadata> {
+        let releases = self.releases()?;
+        let files = releases.get(version).ok_or_else(|| {
+            ErrorKind::VersionDoesntExist(self.info.name.to_owned(), version.clone())
+        })?;
+        let files = self.files_for_python(version, files, target, all_yanked(files));
+        let supported_tags = target.supported_tags();
+        let best_wheel = files
             .iter()
-            .find(|release| release.package_type == release_type)
+            .filter(|release| release.package_type == ReleaseType::BdistWheel)
+            .filter_map(|release| {
+                WheelFilename::parse(&release.filename)
+                    .ok()
+                    .and_then(|wheel| wheel.priority(&supported_tags))
+                    .map(|priority| (priority, *release))
+            })
+            .min_by_key(|&(priority, _)| priority)
+            .map(|(_, release)| release);
+        best_wheel
+            .or_else(|| {
+                files.into_iter().find(|release| {
+                    release.package_type == ReleaseType::Sdist
+                        && release.filename.ends_with(".tar.gz")
+                })
+            })
             .ok_or_else(|| {
-                ErrorKind::NoReleaseForVersion(self.info.name.to_owned(), version.clone())
-            })?;
-        release.get_requires(client)
+                ErrorKind::NoReleaseForVersion(self.info.name.to_owned(), version.clone()).into()
+            })
     }
+
     pub fn get_requires_for_version(
         &self,
         client: &reqwest::Client,
         version: &semver::Version,
+        target: &TargetPython,
+        limits: &ArchiveLimits,
     ) -> Result<Vec<PackageVersionReq>> {
-        for release_type in [ReleaseType::BdistWheel, ReleaseType::Sdist].iter() {
-            let release =
-                self.get_requires_for_version_and_release_type(client, version, *release_type);
-            if release.is_ok() {
-                return release;
-            }
-        }
-        Err(ErrorKind::NoReleaseForVersion(self.info.name.to_owned(), version.clone()).into())
+        self.best_file_for_version(version, target)?
+            .get_requires(client, limits)
+    }
+
+    pub fn get_entry_points_for_version(
+        &self,
+        client: &reqwest::Client,
+        version: &semver::Version,
+        target: &TargetPython,
+        limits: &ArchiveLimits,
+    ) -> Result<EntryPoints> {
+        self.best_file_for_version(version, target)?
+            .get_entry_points(client, limits)
     }
 
     pub fn releases(&self) -> Result<HashMap<semver::Version, &Vec<ReleaseMetadata>>> {
@@ -60,19 +160,141 @@ impl PypiPackage {
             .collect()
     }
 
-    pub fn latest_version(&self) -> Result<semver::Version> {
-        self.releases()
+    /// Versions with at least one file installable on `target`'s python.
+    /// Yanked files only count for the versions in `pinned`.
+    fn versions_for_python(
+        &self,
+        target: &TargetPython,
+        pinned: &[&semver::Version],
+    ) -> Result<Vec<semver::Version>> {
+        Ok(self.releases()?
+            .into_iter()
+            .filter(|&(ref version, files)| {
+                let allow_yanked = pinned.contains(&version);
+                !self.files_for_python(version, files, target, allow_yanked).is_empty()
+            })
+            .map(|(version, _)| version)
+            .collect())
+    }
+
+    /// The newest stable version installable on `target`, or the newest
+    /// pre-release when nothing stable has been published.
+    pub fn latest_version(&self, target: &TargetPython) -> Result<semver::Version> {
+        let versions = self.versions_for_python(target, &[])
             .chain_err(|| {
                 ErrorKind::PackageHasNoReleasedVersions(self.info.name.to_owned())
-            })?
-            .keys()
+            })?;
+        versions
+            .iter()
+            .filter(|version| !is_prerelease(version))
             .max()
-            .map(|x| x.to_owned())
+            .or_else(|| versions.iter().max())
+            .cloned()
             .ok_or_else(|| {
                 ErrorKind::PackageHasNoReleasedVersions(self.info.name.to_owned()).into()
             })
     }
 
+    /// The newest version installable on `target` that satisfies all of `reqs`.
+    /// As in PEP 592, a yanked version is only chosen when pinned with `==`.
+    /// Pre-releases are only considered when `allow_prereleases`, when a
+    /// requirement mentions one, or when nothing else satisfies `reqs`.
+    pub fn best_version_matching(
+        &self,
+        reqs: &[PackageVersionReq],
+        target: &TargetPython,
+        allow_prereleases: bool,
+    ) -> Result<semver::Version> {
+        let pinned: Vec<&semver::Version> =
+            reqs.iter().filter_map(|req| req.pinned_version()).collect();
+        let versions = self.versions_for_python(target, &pinned)?;
+        let best = |allow_prereleases: bool| {
+            versions
+                .iter()
+                .filter(|version| {
+                    reqs.iter()
+                        .all(|req| req.matches_with_prereleases(version, allow_prereleases))
+                })
+                .max()
+                .cloned()
+        };
+        best(allow_prereleases)
+            .or_else(|| best(true))
+            .ok_or_else(|| {
+                ErrorKind::NoVersionMatchingRequirements(
+                    self.info.name.to_owned(),
+                    format!("{:?}", reqs),
+                ).into()
+            })
+    }
+
+    /// The files published for `version`, found without parsing every
+    /// release into a map.
+    fn files_for_version(&self, version: &semver::Version) -> Option<&[ReleaseMetadata]> {
+        self.releases
+            .iter()
+            .find(|&(key, _)| {
+                normalize_and_parse_version_string(key).ok().as_ref() == Some(version)
+            })
+            .map(|(_, files)| files.as_slice())
+    }
+
+    /// Whether every file of `version` has been yanked from the index.
+    pub fn is_yanked(&self, version: &semver::Version) -> bool {
+        self.files_for_version(version).map_or(false, all_yanked)
+    }
+
+    /// Every version that has been yanked, oldest first.
+    pub fn yanked_versions(&self) -> Result<Vec<semver::Version>> {
+        let mut versions: Vec<semver::Version> = self.releases()?
+            .into_iter()
+            .filter(|&(_, files)| all_yanked(files))
+            .map(|(version, _)| version)
+            .collect();
+        versions.sort();
+        Ok(versions)
+    }
+
+    /// Why `version` was yanked, if it was and the maintainers said why.
+    pub fn yanked_reason(&self, version: &semver::Version) -> Option<&str> {
+        let files = self.files_for_version(version).filter(|files| all_yanked(files))?;
+        files
+            .iter()
+            .filter_map(|release| release.yanked_reason.as_ref())
+            .map(|reason| reason.as_str())
+            .find(|reason| reason != &"")
+    }
+
+    /// `sha256:<hex>` hashes of every wheel and sdist published for `version`,
+    /// sorted, as recorded in Pipfile.lock. Yanked files are left out unless
+    /// the whole version was yanked.
+    pub fn hashes_for_version(&self, version: &semver::Version) -> Result<Vec<String>> {
+        let files = self.files_for_version(version).ok_or_else(|| {
+            ErrorKind::VersionDoesntExist(self.info.name.to_owned(), version.clone())
+        })?;
+        let allow_yanked = all_yanked(files);
+        let mut hashes = files
+            .iter()
+            .filter(|release| allow_yanked || !release.yanked)
+            .filter(|release| {
+                release.package_type == ReleaseType::BdistWheel
+                    || release.package_type == ReleaseType::Sdist
+            })
+            .map(|release| release.index_hash())
+            .collect::<Result<Vec<String>>>()?;
+        hashes.sort();
+        hashes.dedup();
+        Ok(hashes)
+    }
+
+    /// The version string exactly as published, eg. `1.0` rather than `1.0.0`.
+    pub fn version_string(&self, version: &semver::Version) -> Option<&str> {
+        self.releases
+            .keys()
+            .find(|key| normalize_and_parse_version_string(key).ok().as_ref() == Some(version))
+            .map(|key| key.as_str())
+    }
+
     pub fn name(&self) -> &str {
         &self.info.name
     }
@@ -86,12 +308,12 @@ struct PackageInfo {
     maintainer_email: Option<String>,
     keywords: Option<String>,
     package_url: String,
-    author: String,
-    author_email: String,
+    author: Option<String>,
+    author_email: Option<String>,
     download_url: Option<String>,
-    platform: String,
+    platform: Option<String>,
     version: String,
-    description: String,
+    description: Option<String>,
     release_url: String,
     downloads: PackageDownloads,
     #[serde(default)] requires_dist: Vec<String>,
@@ -99,37 +321,394 @@ struct PackageInfo {
     name: String,
     bugtrack_url: Option<String>,
     license: Option<String>,
-    summary: String,
-    home_page: String,
+    summary: Option<String>,
+    home_page: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
 struct PackageDownloads {
-    last_month: u64,
-    last_week: u64,
-    last_day: u64,
+    last_month: i64,
+    last_week: i64,
+    last_day: i64,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct ReleaseMetadata {
     has_sig: bool,
     upload_time: String,
-    comment_text: String,
+    comment_text: Option<String>,
     python_version: String,
     url: String,
     md5_digest: String,
-    downloads: u64,
+    #[serde(default)] digests: ReleaseDigests,
+    requires_python: Option<String>,
+    downloads: i64,
     filename: String,
     #[serde(rename = "packagetype")] package_type: ReleaseType,
-    path: String,
+    path: Option<String>,
     size: u64,
+    #[serde(default)] yanked: bool,
+    #[serde(default)] yanked_reason: Option<String>,
 }
+
+/// Every digest PyPI publishes for a release file, as lowercase hex.
+#[derive(Deserialize, Debug, Default)]
+pub struct ReleaseDigests {
+    pub md5: Option<String>,
+    pub sha256: Option<String>,
+    pub blake2b_256: Option<String>,
+}
+
 impl ReleaseMetadata {
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+    pub fn package_type(&self) -> ReleaseType {
+        self.package_type
+    }
+    /// The index's sha256 digest, formatted as `sha256:<hex>`.
+    fn index_hash(&self) -> Result<String> {
+        self.digests
+            .sha256
+            .as_ref()
+            .map(|sha256| format!("sha256:{}", sha256))
+            .ok_or_else(|| ErrorKind::MissingDigest(self.filename.to_owned()).into())
+    }
     fn get_release_file(&self, client: &reqwest::Client) -> Result<reqwest::Response> {
         Ok(client.get(&self.url).send()?)
     }
-    fn get_requires(&self, client: &reqwest::Client) -> Result<Vec<PackageVersionReq>> {
-        let resp = self.get_release_file(client)?;
-        parse_release_requirements(resp, self.package_type)
+    /// Downloads this file to `writer`, rejecting it unless it matches the
+    /// index's sha256 digest and, when any are given, one of the hashes pinned
+    /// in Pipfile.lock. Returns the number of bytes written.
+    pub fn download<W: Write>(
+        &self,
+        client: &reqwest::Client,
+        pinned_hashes: &[String],
+        writer: &mut W,
+    ) -> Result<u64> {
+        let index_hash = self.index_hash()?;
+        if !pinned_hashes.is_empty() && !pinned_hashes.contains(&index_hash) {
+            bail!(ErrorKind::HashMismatch(
+                self.filename.to_owned(),
+                pinned_hashes.join(", "),
+                index_hash
+            ));
+        }
+        copy_verified(
+            self.get_release_file(client)?,
+            writer,
+            &self.filename,
+            &[index_hash],
+        )
+    }
+    /// Downloads this file into memory, checked against the index's digest.
+    fn read(&self, client: &reqwest::Client) -> Result<Vec<u8>> {
+        read_verified(
+            self.get_release_file(client)?,
+            &self.filename,
+            &[self.index_hash()?],
+        )
+    }
+    fn get_requires(
+        &self,
+        client: &reqwest::Client,
+        limits: &ArchiveLimits,
+    ) -> Result<Vec<PackageVersionReq>> {
+        let bytes = self.read(client)?;
+        parse_release_requirements(io::Cursor::new(bytes), self.package_type, limits)
+    }
+    fn get_entry_points(
+        &self,
+        client: &reqwest::Client,
+        limits: &ArchiveLimits,
+    ) -> Result<EntryPoints> {
+        let bytes = self.read(client)?;
+        parse_release_entry_points(io::Cursor::new(bytes), self.package_type, limits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use tags::TargetPython;
+    use pypi::*;
+
+    fn make_release(filename: &str, package_type: &str, sha256: &str) -> String {
+        make_release_for_python(filename, package_type, sha256, "null")
+    }
+
+    fn make_release_for_python(
+        filename: &str,
+        package_type: &str,
+        sha256: &str,
+        requires_python: &str,
+    ) -> String {
+        format!(
+            r#"{{
+                "has_sig": false,
+                "upload_time": "2017-10-18T07:43:36",
+                "comment_text": "",
+                "python_version": "py2.py3",
+                "url": "https://files.pythonhosted.org/packages/{0}",
+                "md5_digest": "d41d8cd98f00b204e9800998ecf8427e",
+                "digests": {{
+                    "md5": "d41d8cd98f00b204e9800998ecf8427e",
+                    "sha256": "{2}",
+                    "blake2b_256": "0e2e"
+                }},
+                "downloads": -1,
+                "filename": "{0}",
+                "packagetype": "{1}",
+                "requires_python": {3},
+                "size": 1024
+            }}"#,
+            filename, package_type, sha256, requires_python
+        )
+    }
+
+    fn make_package(releases: &[(&str, Vec<String>)]) -> PypiPackage {
+        let releases = releases
+            .iter()
+            .map(|&(version, ref files)| format!("\"{}\": [{}]", version, files.join(",")))
+            .collect::<Vec<_>>()
+            .join(",");
+        serde_json::from_str(&format!(
+            r#"{{
+                "info": {{
+                    "maintainer": null,
+                    "docs_url": null,
+                    "requires_python": null,
+                    "maintainer_email": null,
+                    "keywords": null,
+                    "package_url": "https://pypi.org/project/requests/",
+                    "author": "Kenneth Reitz",
+                    "author_email": "me@kennethreitz.org",
+                    "download_url": null,
+                    "platform": "",
+                    "version": "2.18.4",
+                    "description": "",
+                    "release_url": "https://pypi.org/project/requests/2.18.4/",
+                    "downloads": {{"last_month": -1, "last_week": -1, "last_day": -1}},
+                    "classifiers": [],
+                    "name": "requests",
+                    "bugtrack_url": null,
+                    "license": "Apache 2.0",
+                    "summary": "Python HTTP for Humans.",
+                    "home_page": "http://python-requests.org"
+                }},
+                "releases": {{{}}},
+                "urls": []
+            }}"#,
+            releases
+        )).unwrap()
+    }
+
+    #[test]
+    fn best_file_prefers_most_specific_compatible_wheel() {
+        let wheel = |filename: &str| make_release(filename, "bdist_wheel", "00");
+        let package = make_package(&[
+            (
+                "1.13.3",
+                vec![
+                    make_release("numpy-1.13.3.tar.gz", "sdist", "01"),
+                    wheel("numpy-1.13.3-cp27-cp27mu-manylinux1_x86_64.whl"),
+                    wheel("numpy-1.13.3-cp36-none-win_amd64.whl"),
+                    wheel("numpy-1.13.3-py3-none-any.whl"),
+                    wheel("numpy-1.13.3-cp36-cp36m-manylinux1_x86_64.whl"),
+                ],
+            ),
+        ]);
+        let version = semver::Version::parse("1.13.3").unwrap();
+
+        let best = |python: &str, platform: &str| {
+            let target = TargetPython::parse(python, vec![platform.to_owned()]).unwrap();
+            package
+                .best_file_for_version(&version, &target)
+                .unwrap()
+                .filename
+                .to_owned()
+        };
+        assert_eq!(
+            best("3.6", "manylinux1_x86_64"),
+            "numpy-1.13.3-cp36-cp36m-manylinux1_x86_64.whl"
+        );
+        assert_eq!(best("3.6", "macosx_10_6_intel"), "numpy-1.13.3-py3-none-any.whl");
+        assert_eq!(best("2.7", "win32"), "numpy-1.13.3.tar.gz");
+    }
+
+    #[test]
+    fn skip_releases_excluded_by_requires_python() {
+        let package = make_package(&[
+            (
+                "2.0.0",
+                vec![
+                    make_release_for_python("six-2.0.0.tar.gz", "sdist", "01", "\">=3.7\""),
+                ],
+            ),
+            (
+                "1.16.0",
+                vec![
+                    make_release_for_python(
+                        "six-1.16.0.tar.gz",
+                        "sdist",
+                        "02",
+                        "\">=2.7, !=3.0.*, !=3.1.*\"",
+                    ),
+                ],
+            ),
+            ("1.15.0", vec![make_release("six-1.15.0.tar.gz", "sdist", "03")]),
+        ]);
+        let python = |version: &str| TargetPython::parse(version, vec![]).unwrap();
+
+        assert_eq!(
+            package.latest_version(&python("3.6")).unwrap(),
+            semver::Version::parse("1.16.0").unwrap()
+        );
+        assert_eq!(
+            package.latest_version(&python("3.11")).unwrap(),
+            semver::Version::parse("2.0.0").unwrap()
+        );
+        assert_eq!(
+            package
+                .best_version_matching(&[], &python("3.1.4"), false)
+                .unwrap(),
+            semver::Version::parse("1.15.0").unwrap()
+        );
+    }
+
+    #[test]
+    fn python_version_without_micro_matches_any_micro() {
+        let package = make_package(&[
+            (
+                "2.0.0",
+                vec![
+                    make_release_for_python("six-2.0.0.tar.gz", "sdist", "01", "\">=3.6.1\""),
+                ],
+            ),
+            ("1.15.0", vec![make_release("six-1.15.0.tar.gz", "sdist", "03")]),
+        ]);
+        let python = |version: &str| TargetPython::parse(version, vec![]).unwrap();
+
+        assert_eq!(
+            package.latest_version(&python("3.6")).unwrap(),
+            semver::Version::parse("2.0.0").unwrap()
+        );
+        assert_eq!(
+            package.latest_version(&python("3.6.0")).unwrap(),
+            semver::Version::parse("1.15.0").unwrap()
+        );
+    }
+
+    #[test]
+    fn prereleases_only_when_allowed_or_needed() {
+        let package = make_package(&[
+            ("2.0b1", vec![make_release("django-2.0b1.tar.gz", "sdist", "01")]),
+            ("1.11", vec![make_release("django-1.11.tar.gz", "sdist", "02")]),
+        ]);
+        let target = TargetPython::parse("3.6", vec![]).unwrap();
+        let stable = semver::Version::parse("1.11.0").unwrap();
+        let prerelease = semver::Version::parse("2.0.0-b.1").unwrap();
+        let req = |specifier: &str| PackageVersionReq::from_specifier("django", specifier).unwrap();
+
+        assert_eq!(package.latest_version(&target).unwrap(), stable);
+        assert_eq!(package.best_version_matching(&[req("*")], &target, false).unwrap(), stable);
+        assert_eq!(
+            package.best_version_matching(&[req("*")], &target, true).unwrap(),
+            prerelease
+        );
+        assert_eq!(
+            package.best_version_matching(&[req(">=1.12")], &target, false).unwrap(),
+            prerelease
+        );
+        assert_eq!(package.version_string(&prerelease), Some("2.0b1"));
+    }
+
+    #[test]
+    fn post_releases_are_separate_versions() {
+        let package = make_package(&[
+            ("1.0", vec![make_release("six-1.0.tar.gz", "sdist", "01")]),
+            ("1.0.post1", vec![make_release("six-1.0.post1.tar.gz", "sdist", "02")]),
+        ]);
+        let target = TargetPython::parse("3.6", vec![]).unwrap();
+        let post = normalize_and_parse_version_string("1.0.post1").unwrap();
+
+        assert_eq!(package.releases().unwrap().len(), 2);
+        assert_eq!(package.latest_version(&target).unwrap(), post);
+        assert_eq!(package.version_string(&post), Some("1.0.post1"));
+        assert_eq!(package.hashes_for_version(&post).unwrap(), vec!["sha256:02"]);
+        let pinned = PackageVersionReq::from_specifier("six", "==1.0").unwrap();
+        assert_eq!(
+            package.best_version_matching(&[pinned], &target, false).unwrap(),
+            semver::Version::parse("1.0.0").unwrap()
+        );
+    }
+
+    fn yank(release: String, reason: &str) -> String {
+        release.replace(
+            r#""size": 1024"#,
+            &format!(r#""size": 1024, "yanked": true, "yanked_reason": "{}""#, reason),
+        )
+    }
+
+    #[test]
+    fn skip_yanked_releases_unless_pinned() {
+        let package = make_package(&[
+            (
+                "2.1.0",
+                vec![yank(make_release("six-2.1.0.tar.gz", "sdist", "01"), "broken")],
+            ),
+            ("2.0.0", vec![make_release("six-2.0.0.tar.gz", "sdist", "02")]),
+        ]);
+        let target = TargetPython::parse("3.6", vec![]).unwrap();
+        let yanked = semver::Version::parse("2.1.0").unwrap();
+
+        assert_eq!(
+            package.latest_version(&target).unwrap(),
+            semver::Version::parse("2.0.0").unwrap()
+        );
+        let loose = PackageVersionReq::from_specifier("six", ">=2.0").unwrap();
+        assert_eq!(
+            package.best_version_matching(&[loose], &target, false).unwrap(),
+            semver::Version::parse("2.0.0").unwrap()
+        );
+        let pinned = PackageVersionReq::from_specifier("six", "==2.1.0").unwrap();
+        assert_eq!(package.best_version_matching(&[pinned], &target, false).unwrap(), yanked);
+        assert_eq!(package.yanked_versions().unwrap(), vec![yanked.clone()]);
+        assert_eq!(package.yanked_reason(&yanked), Some("broken"));
+    }
+
+    #[test]
+    fn hashes_cover_wheels_and_sdists_sorted() {
+        let package = make_package(&[
+            (
+                "2.18.4",
+                vec![
+                    make_release("requests-2.18.4-py2.py3-none-any.whl", "bdist_wheel", "ff"),
+                    make_release("requests-2.18.4.tar.gz", "sdist", "9c"),
+                    make_release("requests-2.18.4.win32.exe", "bdist_wininst", "aa"),
+                ],
+            ),
+            ("2.18.3", vec![make_release("requests-2.18.3.tar.gz", "sdist", "01")]),
+            (
+                "2.18.2",
+                vec![
+                    yank(make_release("requests-2.18.2.tar.gz", "sdist", "02"), ""),
+                    make_release("requests-2.18.2-py2.py3-none-any.whl", "bdist_wheel", "03"),
+                ],
+            ),
+        ]);
+
+        assert_eq!(
+            package
+                .hashes_for_version(&semver::Version::parse("2.18.4").unwrap())
+                .unwrap(),
+            vec!["sha256:9c".to_owned(), "sha256:ff".to_owned()]
+        );
+        assert_eq!(
+            package
+                .hashes_for_version(&semver::Version::parse("2.18.2").unwrap())
+                .unwrap(),
+            vec!["sha256:03".to_owned()]
+        );
     }
 }
@@ -0,0 +1,142 @@
+use base64;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use errors::*;
+
+/// A line of a dist-info `RECORD` file as described in PEP 376: a path
+/// relative to the directory containing the dist-info, and the file's hash
+/// and size, which are left empty for `RECORD` itself.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RecordEntry {
+    pub path: String,
+    pub hash: Option<String>,
+    pub size: Option<u64>,
+}
+impl RecordEntry {
+    /// The entry for a file with the contents `bytes`.
+    pub fn new(path: &str, bytes: &[u8]) -> RecordEntry {
+        RecordEntry {
+            path: path.to_owned(),
+            hash: Some(record_hash(bytes)),
+            size: Some(bytes.len() as u64),
+        }
+    }
+}
+
+/// `bytes` hashed the way `RECORD` files hash them: `sha256=` followed by the
+/// urlsafe, unpadded base64 of the digest.
+pub fn record_hash(bytes: &[u8]) -> String {
+    format!(
+        "sha256={}",
+        base64::encode_config(&Sha256::digest(bytes), base64::URL_SAFE_NO_PAD)
+    )
+}
+
+/// `bytes` hashed like `record_hash` but with the same algorithm as the
+/// `RECORD` hash `like`, or `None` when that isn't one of the sha256, sha384
+/// or sha512 algorithms PEP 376 allows.
+pub fn record_hash_like(like: &str, bytes: &[u8]) -> Option<String> {
+    let algorithm = like.split('=').next().unwrap_or("");
+    let digest = match algorithm {
+        "sha256" => Sha256::digest(bytes).to_vec(),
+        "sha384" => Sha384::digest(bytes).to_vec(),
+        "sha512" => Sha512::digest(bytes).to_vec(),
+        _ => return None,
+    };
+    Some(format!(
+        "{}={}",
+        algorithm,
+        base64::encode_config(&digest, base64::URL_SAFE_NO_PAD)
+    ))
+}
+
+pub fn parse_record(text: &str) -> Result<Vec<RecordEntry>> {
+    text.lines()
+        .filter(|line| line.trim() != "")
+        .map(|line| {
+            let fields = split_csv_line(line)?;
+            if fields.len() != 3 {
+                bail!("RECORD line doesn't have 3 fields: `{}`", line);
+            }
+            let size = match fields[2].as_str() {
+                "" => None,
+                size => Some(size
+                    .parse()
+                    .chain_err(|| format!("invalid size in RECORD line `{}`", line))?),
+            };
+            Ok(RecordEntry {
+                path: fields[0].to_owned(),
+                hash: Some(fields[1].to_owned()).filter(|x| x != ""),
+                size: size,
+            })
+        })
+        .collect()
+}
+
+pub fn format_record(entries: &[RecordEntry]) -> String {
+    let mut text = String::new();
+    for entry in entries.iter() {
+        text.push_str(&format!(
+            "{},{},{}\n",
+            quote_csv_field(&entry.path),
+            entry.hash.as_ref().map_or("", |x| x.as_str()),
+            entry.size.map_or(String::new(), |x| x.to_string())
+        ));
+    }
+    text
+}
+
+/// Splits a line written by python's `csv` module, which quotes fields
+/// containing commas or quotes and doubles any quotes inside them.
+fn split_csv_line(line: &str) -> Result<Vec<String>> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => fields.push(::std::mem::replace(&mut field, String::new())),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        bail!("unterminated quote in RECORD line `{}`", line);
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+fn quote_csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use record::*;
+
+    #[test]
+    fn round_trip() {
+        let text = "six.py,sha256=4NK0u6YGaxRaJ8bVEbdr7A4S2CmdQ6TyL0Ol0YKBiyE,34549
+\"odd,name.py\",sha256=47DEQpj8HBSa-_TImW-5JCeuQeRkm5NMpJWZG3hSuFU,0
+six-1.16.0.dist-info/RECORD,,
+";
+
+        let entries = parse_record(text).unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1], RecordEntry::new("odd,name.py", b""));
+        assert_eq!(entries[2].hash, None);
+        assert_eq!(entries[2].size, None);
+        assert_eq!(format_record(&entries), text);
+    }
+}
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use serde_json;
 
 use errors::*;
+use markers::group_markers;
 use version_req::PackageVersionReq;
 
 #[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
@@ -34,15 +35,19 @@ pub struct WheelMetadata {
     provides: Option<String>,
 }
 impl WheelMetadata {
+    /// The requirements of every group, with the group's extra and
+    /// environment as markers.
     pub fn to_version_reqs(&self) -> Result<Vec<PackageVersionReq>> {
         self.run_requires
             .iter()
-            .filter(|requires_group| {
-                requires_group.extra == None && requires_group.environment == None
-            })
             .flat_map(|group| {
-                group.requires.iter().map(|requirement| {
+                let markers = group_markers(
+                    group.extra.as_ref().map(|x| x.as_str()),
+                    group.environment.as_ref().map(|x| x.as_str()),
+                );
+                group.requires.iter().map(move |requirement| {
                     PackageVersionReq::parse_requirement(requirement)
+                        .map(|req| req.with_markers(markers.clone()))
                 })
             })
             .collect()
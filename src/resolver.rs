@@ -0,0 +1,321 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use reqwest;
+use semver;
+
+use archive::ArchiveLimits;
+use markers::{Marker, Remaining};
+use pypi::{canonical_name, get_package_data, PypiPackage};
+use semver_utils::normalize_and_parse_version_string;
+use tags::TargetPython;
+use version_req::PackageVersionReq;
+use errors::*;
+
+#[derive(Debug)]
+pub struct ResolvedPackage {
+    pub version: semver::Version,
+    pub version_string: String,
+    pub hashes: Vec<String>,
+    pub extras: Vec<String>,
+    pub markers: Option<String>,
+}
+
+/// Resolves a set of top level requirements into a set of pinned packages.
+///
+/// Each package is pinned to the newest version that satisfies every
+/// requirement seen for it so far; when a new requirement rules out the pinned
+/// version the package is re-pinned and its dependencies walked again. There is
+/// no backtracking, so conflicts that could only be fixed by downgrading some
+/// other package are reported as errors.
+pub struct Resolver<'a> {
+    client: &'a reqwest::Client,
+    target: &'a TargetPython,
+    allow_prereleases: bool,
+    limits: &'a ArchiveLimits,
+    packages: HashMap<String, PypiPackage>,
+}
+impl<'a> Resolver<'a> {
+    /// Only versions installable on `target` are considered, and dependencies
+    /// are read from the release files best suited to it. Pre-releases are
+    /// preferred like any other version when `allow_prereleases`. Release
+    /// files are unpacked within `limits`.
+    pub fn new(
+        client: &'a reqwest::Client,
+        target: &'a TargetPython,
+        allow_prereleases: bool,
+        limits: &'a ArchiveLimits,
+    ) -> Resolver<'a> {
+        Resolver {
+            client: client,
+            target: target,
+            allow_prereleases: allow_prereleases,
+            limits: limits,
+            packages: HashMap::new(),
+        }
+    }
+
+    fn package(&mut self, name: &str) -> Result<&PypiPackage> {
+        let key = canonical_name(name);
+        if !self.packages.contains_key(&key) {
+            let package = get_package_data(self.client, name)?;
+            self.packages.insert(key.clone(), package);
+        }
+        Ok(&self.packages[&key])
+    }
+
+    /// The requirements of `name` at `version`, eg. a version from a lock.
+    pub fn requires(&mut self, name: &str, version: &str) -> Result<Vec<PackageVersionReq>> {
+        let version = normalize_and_parse_version_string(version)?;
+        let client = self.client;
+        let target = self.target;
+        let limits = self.limits;
+        self.package(name)?
+            .get_requires_for_version(client, &version, target, limits)
+    }
+
+    pub fn resolve(
+        &mut self,
+        reqs: Vec<PackageVersionReq>,
+    ) -> Result<BTreeMap<String, ResolvedPackage>> {
+        let mut constraints: HashMap<String, Vec<PackageVersionReq>> = HashMap::new();
+        let mut resolved: BTreeMap<String, ResolvedPackage> = BTreeMap::new();
+        // The extras each package's dependencies were walked with, and what's
+        // left of each dependency's markers once those extras are decided
+        let mut walked_extras: HashMap<String, Vec<String>> = HashMap::new();
+        let mut dependencies: HashMap<String, Vec<(String, Option<String>)>> = HashMap::new();
+        let mut requires_cache: HashMap<(String, semver::Version), Vec<PackageVersionReq>> =
+            HashMap::new();
+        let roots: Vec<(String, Option<String>)> = reqs
+            .iter()
+            .map(|req| (canonical_name(req.name()), req.markers().map(|x| x.to_owned())))
+            .collect();
+        let mut pending: VecDeque<(PackageVersionReq, bool)> =
+            reqs.into_iter().map(|req| (req, true)).collect();
+
+        while let Some((req, is_top_level)) = pending.pop_front() {
+            let key = canonical_name(req.name());
+            let reqs = {
+                let reqs = constraints.entry(key.clone()).or_insert_with(Vec::new);
+                reqs.push(req.clone());
+                reqs.clone()
+            };
+            let extras = {
+                let walked = walked_extras.entry(key.clone()).or_insert_with(Vec::new);
+                let mut extras = walked.clone();
+                for extra in req.extras() {
+                    if !extras.contains(extra) {
+                        extras.push(extra.to_owned());
+                    }
+                }
+                extras
+            };
+            let client = self.client;
+            let target = self.target;
+            let allow_prereleases = self.allow_prereleases;
+            let limits = self.limits;
+            let (version, version_string, hashes, requires) = {
+                let package = self.package(req.name())?;
+                let version = package.best_version_matching(&reqs, target, allow_prereleases)?;
+                let is_repinned = resolved.get(&key).map(|x| &x.version) != Some(&version);
+                if !is_repinned && extras.len() == walked_extras[&key].len() {
+                    let resolved_package = resolved.get_mut(&key).unwrap();
+                    merge_requirement(resolved_package, &req, is_top_level);
+                    continue;
+                }
+                let version_string = package
+                    .version_string(&version)
+                    .unwrap_or(&version.to_string())
+                    .to_owned();
+                if is_repinned && package.is_yanked(&version) {
+                    eprintln!(
+                        "warning: {} {} has been yanked: {}",
+                        package.name(),
+                        version_string,
+                        package.yanked_reason(&version).unwrap_or("no reason given")
+                    );
+                }
+                let hashes = package.hashes_for_version(&version)?;
+                let cache_key = (key.clone(), version.clone());
+                if !requires_cache.contains_key(&cache_key) {
+                    let requires =
+                        package.get_requires_for_version(client, &version, target, limits)?;
+                    requires_cache.insert(cache_key.clone(), requires);
+                }
+                (version, version_string, hashes, requires_cache[&cache_key].clone())
+            };
+
+            let mut edges = vec![];
+            for dependency in requires.into_iter() {
+                let remaining = match dependency.markers() {
+                    Some(markers) => Marker::parse(markers)
+                        .and_then(|x| x.with_extras_decided(&extras))
+                        .chain_err(|| {
+                            format!("invalid markers in the requirements of {}", req.name())
+                        })?,
+                    None => Remaining::Always,
+                };
+                let markers = match remaining {
+                    Remaining::Never => continue,
+                    Remaining::Always => None,
+                    Remaining::When(marker) => Some(marker.to_string()),
+                };
+                edges.push((canonical_name(dependency.name()), markers));
+                pending.push_back((dependency.with_markers(None), false));
+            }
+            dependencies.insert(key.clone(), edges);
+            walked_extras.insert(key.clone(), extras);
+
+            let mut resolved_package = resolved.remove(&key).unwrap_or_else(|| ResolvedPackage {
+                version: version.clone(),
+                version_string: version_string.clone(),
+                hashes: vec![],
+                extras: vec![],
+                markers: None,
+            });
+            resolved_package.version = version;
+            resolved_package.version_string = version_string;
+            resolved_package.hashes = hashes;
+            merge_requirement(&mut resolved_package, &req, is_top_level);
+            resolved.insert(key, resolved_package);
+        }
+
+        // Packages only an earlier pin depended on are no longer needed
+        let mut markers = package_markers(&roots, &dependencies)?;
+        Ok(resolved
+            .into_iter()
+            .filter_map(|(key, mut package)| {
+                let package_markers = markers.remove(&key)?;
+                package.markers = package_markers;
+                Some((key, package))
+            })
+            .collect())
+    }
+}
+
+fn merge_requirement(package: &mut ResolvedPackage, req: &PackageVersionReq, is_top_level: bool) {
+    if is_top_level {
+        for extra in req.extras() {
+            if !package.extras.contains(extra) {
+                package.extras.push(extra.to_owned());
+            }
+        }
+        package.extras.sort();
+    }
+}
+
+/// The markers under which each package reachable from the top level `roots`
+/// is needed, `None` for those always needed. A package is needed when all
+/// the markers along any one path of `dependencies` to it hold.
+fn package_markers(
+    roots: &[(String, Option<String>)],
+    dependencies: &HashMap<String, Vec<(String, Option<String>)>>,
+) -> Result<HashMap<String, Option<String>>> {
+    // Each path as the set of markers along it. A path needing more than
+    // another to the same package adds nothing, which also ends cycles.
+    let mut paths: HashMap<String, Vec<BTreeSet<String>>> = HashMap::new();
+    let mut pending: VecDeque<(String, BTreeSet<String>)> = roots
+        .iter()
+        .map(|&(ref key, ref markers)| (key.to_owned(), markers.iter().cloned().collect()))
+        .collect();
+    while let Some((key, path)) = pending.pop_front() {
+        {
+            let known = paths.entry(key.clone()).or_insert_with(Vec::new);
+            if known.iter().any(|x| x.is_subset(&path)) {
+                continue;
+            }
+            known.retain(|x| !path.is_subset(x));
+            known.push(path.clone());
+        }
+        for &(ref dependency, ref markers) in dependencies.get(&key).into_iter().flatten() {
+            let mut dependency_path = path.clone();
+            dependency_path.extend(markers.iter().cloned());
+            pending.push_back((dependency.to_owned(), dependency_path));
+        }
+    }
+
+    let mut markers = HashMap::new();
+    for (key, mut paths) in paths.into_iter() {
+        paths.sort();
+        let package_markers = if paths.iter().any(|x| x.is_empty()) {
+            None
+        } else if paths.len() == 1 && paths[0].len() == 1 {
+            paths[0].iter().next().cloned()
+        } else {
+            let mut alternatives = vec![];
+            for path in paths.iter() {
+                let mut conditions = vec![];
+                for markers in path.iter() {
+                    conditions.push(Marker::parse(markers)?);
+                }
+                alternatives.push(join(conditions, Marker::And));
+            }
+            Some(join(alternatives, Marker::Or).to_string())
+        };
+        markers.insert(key, package_markers);
+    }
+    Ok(markers)
+}
+
+/// Combines non-empty `markers` pairwise with `combine`, eg. `Marker::And`.
+fn join<F>(markers: Vec<Marker>, combine: F) -> Marker
+where
+    F: Fn(Box<Marker>, Box<Marker>) -> Marker,
+{
+    let mut markers = markers.into_iter();
+    let first = markers.next().expect("no markers to join");
+    markers.fold(first, |joined, marker| combine(Box::new(joined), Box::new(marker)))
+}
+
+#[cfg(test)]
+mod tests {
+    use resolver::*;
+
+    fn edge(key: &str, markers: Option<&str>) -> (String, Option<String>) {
+        (key.to_owned(), markers.map(|x| x.to_owned()))
+    }
+
+    #[test]
+    fn markers_follow_every_path() {
+        let roots = vec![
+            edge("requests", None),
+            edge("pywin32", Some("os_name == 'nt'")),
+        ];
+        let mut dependencies = HashMap::new();
+        dependencies.insert(
+            "requests".to_owned(),
+            vec![
+                edge("idna", None),
+                edge("win-inet-pton", Some("sys_platform == \"win32\"")),
+                edge("importlib-metadata", Some("python_version < \"3.8\"")),
+            ],
+        );
+        dependencies.insert(
+            "pywin32".to_owned(),
+            vec![
+                edge("win-inet-pton", Some("python_version < \"3.8\"")),
+                edge("idna", None),
+            ],
+        );
+        // A cycle back to the top
+        dependencies.insert(
+            "importlib-metadata".to_owned(),
+            vec![edge("requests", Some("extra == \"never\""))],
+        );
+
+        let markers = package_markers(&roots, &dependencies).unwrap();
+
+        assert_eq!(markers["requests"], None);
+        assert_eq!(markers["idna"], None);
+        assert_eq!(markers["pywin32"], Some("os_name == 'nt'".to_owned()));
+        assert_eq!(
+            markers["importlib-metadata"],
+            Some("python_version < \"3.8\"".to_owned())
+        );
+        assert_eq!(
+            markers["win-inet-pton"],
+            Some(
+                "os_name == \"nt\" and python_version < \"3.8\" or sys_platform == \"win32\""
+                    .to_owned()
+            )
+        );
+    }
+}
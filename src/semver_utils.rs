@@ -14,9 +14,31 @@ fn match_to_int(capture: Option<regex::Match>) -> u16 {
     }
 }
 
+/// Maps the PEP 440 spellings of a pre-release tag onto its normal form.
+fn normalize_pre_tag(tag: &str) -> &'static str {
+    match tag.to_lowercase().as_str() {
+        "a" | "alpha" => "a",
+        "b" | "beta" => "b",
+        _ => "rc",
+    }
+}
+
+/// Rewrites a PEP 440 version as semver: missing components become `0` and
+/// anything left over is ignored. Pre-releases such as `2.0b1` become semver
+/// pre-release identifiers (`2.0.0-b.1`). semver has no later-than-release
+/// suffix, so a post-release such as `1.0.post1` becomes a pre-release of the
+/// next patch version whose first identifier is `0` (`1.0.1-0.1`), which
+/// orders before anything else published for that patch version. A
+/// development release such as `1.0.dev3` becomes `1.0.0-1.3`, ordering after
+/// those post-releases and before alphas.
 fn normalize_version_string(version: &str) -> Result<String> {
     lazy_static! {
-        static ref RE: Regex = Regex::new(r"^(\d+).?(\d+)?.?(\d+)?(.*)$").unwrap();
+        static ref RE: Regex = Regex::new(concat!(
+            r"(?i)^v?(\d+)(?:\.(\d+))?(?:\.(\d+))?(?:\.\d+)*",
+            r"(?:[-_.]?(a|alpha|b|beta|c|rc|pre|preview)[-_.]?(\d*))?",
+            r"(?:[-_.]?(?:post|rev|r)[-_.]?(\d*)|-(\d+))?",
+            r"(?:[-_.]?(dev)[-_.]?(\d*))?",
+        )).unwrap();
     }
     let captures = RE.captures(version).ok_or_else(|| {
         ErrorKind::NormalizeVersionStringRegexFailed(version.to_owned())
@@ -24,15 +46,64 @@ fn normalize_version_string(version: &str) -> Result<String> {
 
     let maj: u16 = match_to_int(captures.get(1));
     let min: u16 = match_to_int(captures.get(2));
-    let patch: u16 = match_to_int(captures.get(3));
+    let mut patch = match_to_int(captures.get(3)) as u32;
+    let post = captures
+        .get(6)
+        .or_else(|| captures.get(7))
+        .map(|post| match_to_int(Some(post)));
+    let dev = captures.get(8).map(|_| match_to_int(captures.get(9)));
+
+    let mut pre = vec![];
+    match (captures.get(4), post, dev) {
+        (Some(tag), post, dev) => {
+            pre.push(normalize_pre_tag(tag.as_str()).to_owned());
+            pre.push(match_to_int(captures.get(5)).to_string());
+            if let Some(post) = post {
+                pre.push(format!("post.{}", post));
+            }
+            if let Some(dev) = dev {
+                pre.push(format!("dev.{}", dev));
+            }
+        }
+        (None, Some(post), None) => {
+            patch += 1;
+            pre.push(format!("0.{}", post));
+        }
+        // A development release of a post-release comes after the previous
+        // post-release and before the one it leads up to
+        (None, Some(post), Some(dev)) if post > 0 => {
+            patch += 1;
+            pre.push(format!("0.{}.1.{}", post - 1, dev));
+        }
+        (None, _, Some(dev)) => pre.push(format!("1.{}", dev)),
+        (None, _, None) => (),
+    }
+    let mut normalized = format!("{}.{}.{}", maj, min, patch);
+    if !pre.is_empty() {
+        normalized.push_str(&format!("-{}", pre.join(".")));
+    }
+    Ok(normalized)
+}
 
-    Ok(format!("{}.{}.{}", maj, min, patch))
+/// Whether `version` is a PEP 440 pre-release or development release. Unlike
+/// `semver::Version::is_prerelease` this is false for post-releases, which
+/// `normalize_version_string` spells as semver pre-releases.
+pub fn is_prerelease(version: &semver::Version) -> bool {
+    match version.pre.first() {
+        Some(&semver::Identifier::Numeric(0)) => version.pre.len() != 2,
+        Some(_) => true,
+        None => false,
+    }
 }
 
+/// Parses a version as found on PyPI or in a specifier. Plain semver is taken
+/// as is; anything else, including every PEP 440 pre-release, is normalized.
 pub fn normalize_and_parse_version_string(version: &str) -> Result<semver::Version> {
     match semver::Version::parse(version) {
-        Ok(version) => Ok(version),
-        Err(_) => semver::Version::parse(&normalize_version_string(version)?).map_err(|e| e.into()),
+        Ok(ref version) if !version.is_prerelease() && version.build.is_empty() => {
+            Ok(version.clone())
+        }
+        _ => semver::Version::parse(&normalize_version_string(version)?).map_err(|e| e.into()),
     }
 }
 
@@ -66,4 +137,39 @@ mod tests {
 
         assert_eq!(fixed, "5.0.0");
     }
+
+    #[test]
+    fn keep_prerelease_tags() {
+        assert_eq!(normalize_version_string("2.0b1").unwrap(), "2.0.0-b.1");
+        assert_eq!(normalize_version_string("1.0.0RC2").unwrap(), "1.0.0-rc.2");
+        assert_eq!(normalize_version_string("1.1.dev3").unwrap(), "1.1.0-1.3");
+        assert!(
+            normalize_and_parse_version_string("2.0a1").unwrap()
+                < normalize_and_parse_version_string("2.0b1").unwrap()
+        );
+        assert!(
+            normalize_and_parse_version_string("2.0rc1").unwrap()
+                < normalize_and_parse_version_string("2.0").unwrap()
+        );
+    }
+    #[test]
+    fn order_post_and_dev_releases() {
+        let versions: Vec<semver::Version> = [
+            "0.9.post2", "1.0.dev3", "1.0a1", "1.0a1.post1", "1.0b1", "1.0rc1", "1.0",
+            "1.0.post1.dev2", "1.0.post1", "1.0.post2", "1.0.1.dev1", "1.0.1",
+        ].iter()
+            .map(|x| normalize_and_parse_version_string(x).unwrap())
+            .collect();
+        let mut sorted = versions.clone();
+        sorted.sort();
+        sorted.dedup();
+
+        assert_eq!(sorted, versions);
+        assert_eq!(normalize_version_string("1.0.post1").unwrap(), "1.0.1-0.1");
+        let prereleases: Vec<bool> = versions.iter().map(is_prerelease).collect();
+        assert_eq!(
+            prereleases,
+            vec![false, true, true, true, true, true, false, true, false, false, true, false]
+        );
+    }
 }
@@ -0,0 +1,368 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use rayon::prelude::*;
+use reqwest;
+use semver;
+use tempdir::TempDir;
+
+use archive::ArchiveLimits;
+use install_wheel::install_wheel;
+use installed::{installed_distributions, InstalledDistribution, MetadataKind};
+use interpreters::InterpreterInfo;
+use markers::markers_apply;
+use pipfile::{lockfile_category, LockedPackage, Lockfile};
+use pypi::{canonical_name, get_package_data};
+use release::ReleaseType;
+use semver_utils::normalize_and_parse_version_string;
+use tags::{host_platforms, TargetPython};
+use uninstall::uninstall;
+use venv::Virtualenv;
+use errors::*;
+
+/// The locked packages of the Pipfile `categories` whose markers hold for
+/// `interpreter`. A package locked in several categories is taken from the
+/// first that has it.
+pub fn locked_packages<'a>(
+    lockfile: &'a Lockfile,
+    categories: &[String],
+    interpreter: &InterpreterInfo,
+) -> Result<BTreeMap<String, &'a LockedPackage>> {
+    let mut packages = BTreeMap::new();
+    for category in categories.iter() {
+        let locked = lockfile
+            .category(lockfile_category(category))
+            .ok_or_else(|| format!("[{}] isn't in Pipfile.lock", category))?;
+        for (name, package) in locked.iter() {
+            let markers = package.markers.as_ref().map(|x| x.as_str());
+            if markers_apply(markers, &interpreter.markers)
+                .chain_err(|| format!("failed to evaluate the markers of {}", name))?
+            {
+                packages.entry(name.to_owned()).or_insert(package);
+            }
+        }
+    }
+    Ok(packages)
+}
+
+/// The version of each of `distributions`, keyed by canonical name.
+fn installed_versions(
+    distributions: &[InstalledDistribution],
+) -> HashMap<String, semver::Version> {
+    distributions
+        .iter()
+        .filter_map(|distribution| {
+            normalize_and_parse_version_string(&distribution.version)
+                .ok()
+                .map(|version| (canonical_name(&distribution.name), version))
+        })
+        .collect()
+}
+
+/// Distributions `clean` never removes, since they're part of a working
+/// environment rather than the project.
+const ENVIRONMENT_DISTRIBUTIONS: &[&str] = &["pip", "setuptools", "wheel", "distribute"];
+
+/// The `distributions` that aren't locked in any of the Pipfile
+/// `categories`, or in any section of Pipfile.lock when there are none.
+pub fn extraneous_distributions<'a>(
+    distributions: &'a [InstalledDistribution],
+    lockfile: &Lockfile,
+    categories: Option<&[String]>,
+) -> Result<Vec<&'a InstalledDistribution>> {
+    let sections: Vec<&str> = match categories {
+        Some(categories) => categories.iter().map(|x| lockfile_category(x)).collect(),
+        None => lockfile.categories.keys().map(|x| x.as_str()).collect(),
+    };
+    let mut locked = HashSet::new();
+    for section in sections {
+        let packages = lockfile
+            .category(section)
+            .ok_or_else(|| format!("[{}] isn't in Pipfile.lock", section))?;
+        locked.extend(packages.keys().map(|name| canonical_name(name)));
+    }
+    Ok(distributions
+        .iter()
+        .filter(|distribution| {
+            let name = canonical_name(&distribution.name);
+            !locked.contains(&name) && !ENVIRONMENT_DISTRIBUTIONS.contains(&name.as_str())
+        })
+        .collect())
+}
+
+/// What `sync` does with one locked package.
+#[derive(Debug, PartialEq)]
+pub enum SyncAction {
+    Install(semver::Version),
+    /// Another version is installed, which is uninstalled first.
+    Replace(semver::Version),
+    UpToDate,
+    /// Packages locked to a git checkout, a path or a URL can't be installed
+    /// yet, and are skipped with this reason.
+    Unsupported(String),
+}
+
+/// Decides what to do with each of `packages` given the distributions
+/// already `installed`.
+pub fn plan_sync<'a>(
+    packages: &BTreeMap<String, &'a LockedPackage>,
+    installed: &HashMap<String, semver::Version>,
+) -> Result<Vec<(String, &'a LockedPackage, SyncAction)>> {
+    let mut plan = vec![];
+    for (name, package) in packages.iter() {
+        let action = match locked_version(package)? {
+            None => SyncAction::Unsupported(match (&package.git, &package.path, &package.file) {
+                (&Some(_), _, _) => "installing from git isn't supported".to_owned(),
+                (_, &Some(_), _) => "installing from a path isn't supported".to_owned(),
+                (_, _, &Some(_)) => "installing from a URL isn't supported".to_owned(),
+                _ => "it isn't locked to a version".to_owned(),
+            }),
+            Some(version) => match installed.get(&canonical_name(name)) {
+                None => SyncAction::Install(version),
+                Some(installed) if *installed == version => SyncAction::UpToDate,
+                Some(_) => SyncAction::Replace(version),
+            },
+        };
+        plan.push((name.to_owned(), *package, action));
+    }
+    Ok(plan)
+}
+
+/// The version an index package is pinned to, eg. `==2.18.4`.
+fn locked_version(package: &LockedPackage) -> Result<Option<semver::Version>> {
+    if package.git.is_some() || package.path.is_some() || package.file.is_some() {
+        return Ok(None);
+    }
+    match package.version {
+        Some(ref version) if version.starts_with("==") => Ok(Some(
+            normalize_and_parse_version_string(version.trim_left_matches("==").trim())?,
+        )),
+        _ => Ok(None),
+    }
+}
+
+/// Downloads the wheel of `name` at `version` best suited to `target` into
+/// `dir`, checking it against the hashes pinned in `package`. Returns the
+/// wheel's filename and where it was saved.
+fn download_wheel(
+    client: &reqwest::Client,
+    target: &TargetPython,
+    name: &str,
+    package: &LockedPackage,
+    version: &semver::Version,
+    dir: &Path,
+) -> Result<(String, PathBuf)> {
+    let package_data = get_package_data(client, name)?;
+    let release = package_data.best_file_for_version(version, target)?;
+    if release.package_type() != ReleaseType::BdistWheel {
+        bail!(
+            "{} {} has no wheel for this python, and building sdists isn't supported",
+            name,
+            version
+        );
+    }
+    let path = dir.join(release.filename());
+    let mut file =
+        File::create(&path).chain_err(|| format!("failed to create {}", path.display()))?;
+    release.download(client, &package.hashes, &mut file)?;
+    Ok((release.filename().to_owned(), path))
+}
+
+/// Counts of what `sync` did.
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncSummary {
+    pub installed: usize,
+    pub up_to_date: usize,
+    pub skipped: usize,
+}
+
+/// Installs `packages` into `venv`, whose interpreter is `interpreter`,
+/// skipping those already installed at the locked version. Packages whose
+/// canonical name is in `requested` are marked as asked for by the user.
+/// Wheels are unpacked within `limits`.
+pub fn sync(
+    client: &reqwest::Client,
+    venv: &Virtualenv,
+    interpreter: &InterpreterInfo,
+    packages: &BTreeMap<String, &LockedPackage>,
+    requested: &HashSet<String>,
+    limits: &ArchiveLimits,
+) -> Result<SyncSummary> {
+    let target = TargetPython::parse(&interpreter.version, host_platforms()?)?;
+    let distributions = installed_distributions(&venv.site_packages())?;
+    let plan = plan_sync(packages, &installed_versions(&distributions))?;
+    let mut summary = SyncSummary::default();
+    let mut to_install = vec![];
+    for (name, package, action) in plan {
+        // As locked, eg. `requests==2.18.4`
+        let pinned = format!("{}{}", name, package.version.as_ref().map_or("", |x| x));
+        match action {
+            SyncAction::Install(version) => to_install.push((name, pinned, package, version, None)),
+            SyncAction::Replace(version) => {
+                let replaced = distributions
+                    .iter()
+                    .find(|x| canonical_name(&x.name) == canonical_name(&name));
+                if let Some(replaced) = replaced.filter(|x| x.kind != MetadataKind::DistInfo) {
+                    // Without a RECORD there's no knowing what to uninstall
+                    eprintln!(
+                        "warning: skipping {} {}: it has no RECORD",
+                        replaced.name, replaced.version
+                    );
+                    summary.skipped += 1;
+                    continue;
+                }
+                to_install.push((name, pinned, package, version, replaced));
+            }
+            SyncAction::UpToDate => summary.up_to_date += 1,
+            SyncAction::Unsupported(reason) => {
+                eprintln!("warning: skipping {}: {}", name, reason);
+                summary.skipped += 1;
+            }
+        }
+    }
+
+    // Wheels are downloaded in parallel to files that are removed afterwards
+    let download_dir = TempDir::new("pipenv-rs")?;
+    let downloads = to_install
+        .par_iter()
+        .map(|&(ref name, ref pinned, package, ref version, _)| {
+            download_wheel(client, &target, name, package, version, download_dir.path())
+                .chain_err(|| format!("failed to download {}", pinned))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let scheme = venv.install_scheme();
+    for ((name, pinned, _, _, replaced), (filename, path)) in
+        to_install.into_iter().zip(downloads)
+    {
+        if let Some(replaced) = replaced {
+            uninstall(replaced, &venv.prefix)?;
+            println!("uninstalled {} {}", replaced.name, replaced.version);
+        }
+        install_wheel(
+            File::open(&path)?,
+            &filename,
+            &scheme,
+            &venv.python(),
+            requested.contains(&canonical_name(&name)),
+            limits,
+        ).chain_err(|| format!("failed to install {}", pinned))?;
+        println!("installed {}", pinned);
+        summary.installed += 1;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use serde_json;
+    use installed::MetadataKind;
+    use interpreters::MarkerEnvironment;
+    use sync::*;
+
+    fn interpreter() -> InterpreterInfo {
+        InterpreterInfo {
+            executable: "/usr/bin/python3.10".into(),
+            version: "3.10.4".to_owned(),
+            implementation: "cpython".to_owned(),
+            markers: MarkerEnvironment {
+                os_name: "posix".to_owned(),
+                python_version: "3.10".to_owned(),
+                ..MarkerEnvironment::default()
+            },
+        }
+    }
+
+    fn lockfile() -> Lockfile {
+        serde_json::from_str(
+            r#"{
+                "_meta": {"hash": {"sha256": "x"}, "requires": {}, "sources": []},
+                "default": {
+                    "requests": {"version": "==2.18.4", "hashes": ["sha256:a"]},
+                    "pywin32": {"version": "==220", "markers": "os_name == 'nt'"},
+                    "mypkg": {"path": ".", "editable": true}
+                },
+                "develop": {
+                    "requests": {"version": "==2.0.0"},
+                    "pytest": {"version": "==3.2.3", "markers": "python_version >= '3'"}
+                }
+            }"#,
+        ).unwrap()
+    }
+
+    #[test]
+    fn select_locked_packages_for_interpreter() {
+        let lockfile = lockfile();
+        let categories = vec!["packages".to_owned(), "dev-packages".to_owned()];
+
+        let packages = locked_packages(&lockfile, &categories, &interpreter()).unwrap();
+
+        assert_eq!(
+            packages.keys().collect::<Vec<_>>(),
+            vec!["mypkg", "pytest", "requests"]
+        );
+        assert_eq!(packages["requests"].version, Some("==2.18.4".to_owned()));
+        assert!(locked_packages(&lockfile, &["docs".to_owned()], &interpreter()).is_err());
+    }
+
+    #[test]
+    fn plan_skips_installed_packages() {
+        let lockfile = lockfile();
+        let categories = vec!["packages".to_owned(), "dev-packages".to_owned()];
+        let packages = locked_packages(&lockfile, &categories, &interpreter()).unwrap();
+        let mut installed = HashMap::new();
+        installed.insert("pytest".to_owned(), semver::Version::parse("3.2.3").unwrap());
+
+        let plan: BTreeMap<String, SyncAction> = plan_sync(&packages, &installed)
+            .unwrap()
+            .into_iter()
+            .map(|(name, _, action)| (name, action))
+            .collect();
+
+        assert_eq!(plan["pytest"], SyncAction::UpToDate);
+        assert_eq!(
+            plan["requests"],
+            SyncAction::Install(semver::Version::parse("2.18.4").unwrap())
+        );
+        match plan["mypkg"] {
+            SyncAction::Unsupported(_) => (),
+            ref action => panic!("unexpected action {:?}", action),
+        }
+
+        installed.insert("requests".to_owned(), semver::Version::parse("2.0.0").unwrap());
+        let plan = plan_sync(&packages, &installed).unwrap();
+        assert_eq!(
+            plan.iter().find(|x| x.0 == "requests").unwrap().2,
+            SyncAction::Replace(semver::Version::parse("2.18.4").unwrap())
+        );
+    }
+
+    #[test]
+    fn find_extraneous_distributions() {
+        let lockfile = lockfile();
+        let distributions: Vec<InstalledDistribution> = ["Requests", "pytest", "six", "pip"]
+            .iter()
+            .map(|name| InstalledDistribution {
+                name: name.to_string(),
+                version: "1.0".to_owned(),
+                path: format!("{}-1.0.dist-info", name).into(),
+                kind: MetadataKind::DistInfo,
+                installer: None,
+                requested: false,
+                direct_url: None,
+            })
+            .collect();
+        let names = |categories: Option<&[String]>| -> Vec<String> {
+            extraneous_distributions(&distributions, &lockfile, categories)
+                .unwrap()
+                .iter()
+                .map(|x| x.name.to_owned())
+                .collect()
+        };
+
+        assert_eq!(names(None), vec!["six"]);
+        assert_eq!(names(Some(&["packages".to_owned()])), vec!["pytest", "six"]);
+        assert!(extraneous_distributions(&distributions, &lockfile, Some(&["docs".to_owned()]))
+            .is_err());
+    }
+}
@@ -0,0 +1,289 @@
+use std::env::consts;
+use std::fmt;
+use std::process::Command;
+use regex::Regex;
+use semver;
+
+use manylinux;
+use errors::*;
+
+/// A single PEP 425 compatibility tag, eg. `cp36-cp36m-manylinux1_x86_64`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Tag {
+    pub interpreter: String,
+    pub abi: String,
+    pub platform: String,
+}
+impl Tag {
+    pub fn new(interpreter: &str, abi: &str, platform: &str) -> Tag {
+        Tag {
+            interpreter: interpreter.to_owned(),
+            abi: abi.to_owned(),
+            platform: platform.to_owned(),
+        }
+    }
+}
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}-{}", self.interpreter, self.abi, self.platform)
+    }
+}
+
+/// The CPython a project is being resolved or installed for.
+#[derive(Debug, Clone)]
+pub struct TargetPython {
+    pub major: u32,
+    pub minor: u32,
+    /// `None` when only `python_version` is known, so the target could be
+    /// any micro version.
+    pub patch: Option<u32>,
+    /// Platform tags supported by the target machine, most specific first.
+    pub platforms: Vec<String>,
+}
+impl TargetPython {
+    /// `version` is a `python_version` or `python_full_version` as written in
+    /// a Pipfile, eg. `3.6` or `3.6.3`.
+    pub fn parse(version: &str, platforms: Vec<String>) -> Result<TargetPython> {
+        let mut parts = version.trim().split('.').map(|x| x.parse::<u32>());
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(major)), Some(Ok(minor)), None) => Ok(TargetPython {
+                major: major,
+                minor: minor,
+                patch: None,
+                platforms: platforms,
+            }),
+            (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch))) => Ok(TargetPython {
+                major: major,
+                minor: minor,
+                patch: Some(patch),
+                platforms: platforms,
+            }),
+            _ => bail!(ErrorKind::InvalidPythonVersion(version.to_owned())),
+        }
+    }
+
+    /// The versions a `Requires-Python` specifier is checked against: the
+    /// target's own, or without a micro version `.0` plus every micro version
+    /// `requires_python` mentions for this `major.minor` and the one after it,
+    /// which is enough to find any micro version that satisfies it.
+    pub fn python_versions(&self, requires_python: &str) -> Vec<semver::Version> {
+        lazy_static! {
+            static ref VERSION_RE: Regex = Regex::new(r"(\d+)\.(\d+)\.(\d+)").unwrap();
+        }
+        let version = |patch: u32| {
+            semver::Version::new(self.major as u64, self.minor as u64, patch as u64)
+        };
+        if let Some(patch) = self.patch {
+            return vec![version(patch)];
+        }
+        let mut versions = vec![version(0)];
+        for captures in VERSION_RE.captures_iter(requires_python) {
+            let part = |i: usize| captures[i].parse::<u32>().ok();
+            if let (Some(major), Some(minor), Some(patch)) = (part(1), part(2), part(3)) {
+                if (major, minor) == (self.major, self.minor) {
+                    versions.push(version(patch));
+                    versions.push(version(patch.saturating_add(1)));
+                }
+            }
+        }
+        versions
+    }
+
+    fn cpython_abi(&self) -> String {
+        // pymalloc builds carried an `m` abi flag until 3.8 dropped it
+        if (self.major, self.minor) < (3, 8) {
+            format!("cp{}{}m", self.major, self.minor)
+        } else {
+            format!("cp{}{}", self.major, self.minor)
+        }
+    }
+
+    /// `py36`, `py3`, `py35`, ..., `py30`: generic python tags from most to
+    /// least specific.
+    fn py_interpreter_range(&self) -> Vec<String> {
+        let mut interpreters = vec![
+            format!("py{}{}", self.major, self.minor),
+            format!("py{}", self.major),
+        ];
+        for minor in (0..self.minor).rev() {
+            interpreters.push(format!("py{}{}", self.major, minor));
+        }
+        interpreters
+    }
+
+    /// Every tag a wheel may have to be installable on this target, ordered
+    /// from most to least preferred, following the order `pip` uses.
+    pub fn supported_tags(&self) -> Vec<Tag> {
+        let interpreter = format!("cp{}{}", self.major, self.minor);
+        let mut tags = vec![];
+        for abi in [self.cpython_abi(), "abi3".to_owned(), "none".to_owned()].iter() {
+            for platform in self.platforms.iter() {
+                tags.push(Tag::new(&interpreter, abi, platform));
+            }
+        }
+        // The stable abi is forward compatible, so older abi3 wheels work too
+        for minor in (2..self.minor).rev() {
+            for platform in self.platforms.iter() {
+                tags.push(Tag::new(
+                    &format!("cp{}{}", self.major, minor),
+                    "abi3",
+                    platform,
+                ));
+            }
+        }
+        for py_interpreter in self.py_interpreter_range().iter() {
+            for platform in self.platforms.iter() {
+                tags.push(Tag::new(py_interpreter, "none", platform));
+            }
+        }
+        tags.push(Tag::new(&interpreter, "none", "any"));
+        for py_interpreter in self.py_interpreter_range().iter() {
+            tags.push(Tag::new(py_interpreter, "none", "any"));
+        }
+        tags
+    }
+}
+
+/// The platform tags supported by the machine we're running on, most specific
+/// first. On windows this is just the generic tag, eg. `win_amd64`.
+pub fn host_platforms() -> Result<Vec<String>> {
+    match (consts::OS, consts::ARCH) {
+        ("linux", _) => Ok(manylinux::host_platforms()),
+        ("windows", "x86_64") => Ok(vec!["win_amd64".to_owned()]),
+        ("windows", "x86") => Ok(vec!["win32".to_owned()]),
+        ("macos", arch @ "x86_64") | ("macos", arch @ "aarch64") => {
+            Ok(mac_platforms(macos_version()?, arch))
+        }
+        (os, arch) => bail!(ErrorKind::UnsupportedPlatform(os.to_owned(), arch.to_owned())),
+    }
+}
+
+/// The running macOS `major.minor` version, from `sw_vers`.
+fn macos_version() -> Result<(u32, u32)> {
+    let output = Command::new("sw_vers")
+        .arg("-productVersion")
+        .output()
+        .chain_err(|| "failed to run sw_vers")?;
+    let version = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    let mut parts = version.split('.').map(|x| x.parse::<u32>());
+    match (parts.next(), parts.next()) {
+        (Some(Ok(major)), Some(Ok(minor))) => Ok((major, minor)),
+        (Some(Ok(major)), None) => Ok((major, 0)),
+        _ => bail!("unexpected macOS version from sw_vers: `{}`", version),
+    }
+}
+
+/// The `macosx_<major>_<minor>_<format>` tags a mac running macOS `version`
+/// on `arch` can install, following the order `pip` uses. From macOS 11 only
+/// the major version counts, and arm64 macs can only install macOS 10 wheels
+/// that are `universal2`.
+fn mac_platforms(version: (u32, u32), arch: &str) -> Vec<String> {
+    let formats: &[&str] = if arch == "aarch64" {
+        &["arm64", "universal2"]
+    } else {
+        &["x86_64", "intel", "fat64", "fat32", "universal2", "universal"]
+    };
+    let mut versions = vec![];
+    if version.0 >= 11 {
+        versions.extend((11..version.0 + 1).rev().map(|major| (major, 0)));
+        versions.extend((4..17).rev().map(|minor| (10, minor)));
+    } else {
+        versions.extend((4..version.1 + 1).rev().map(|minor| (10, minor)));
+    }
+    let mut platforms = vec![];
+    for &(major, minor) in versions.iter() {
+        for format in formats.iter() {
+            if major == 10 && arch == "aarch64" && *format != "universal2" {
+                continue;
+            }
+            platforms.push(format!("macosx_{}_{}_{}", major, minor, format));
+        }
+    }
+    platforms
+}
+
+#[cfg(test)]
+mod tests {
+    use tags::*;
+
+    #[test]
+    fn supported_tags_order() {
+        let target = TargetPython::parse("3.6.3", vec!["manylinux1_x86_64".to_owned()]).unwrap();
+
+        let tags: Vec<String> = target
+            .supported_tags()
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect();
+
+        assert_eq!(
+            tags,
+            vec![
+                "cp36-cp36m-manylinux1_x86_64",
+                "cp36-abi3-manylinux1_x86_64",
+                "cp36-none-manylinux1_x86_64",
+                "cp35-abi3-manylinux1_x86_64",
+                "cp34-abi3-manylinux1_x86_64",
+                "cp33-abi3-manylinux1_x86_64",
+                "cp32-abi3-manylinux1_x86_64",
+                "py36-none-manylinux1_x86_64",
+                "py3-none-manylinux1_x86_64",
+                "py35-none-manylinux1_x86_64",
+                "py34-none-manylinux1_x86_64",
+                "py33-none-manylinux1_x86_64",
+                "py32-none-manylinux1_x86_64",
+                "py31-none-manylinux1_x86_64",
+                "py30-none-manylinux1_x86_64",
+                "cp36-none-any",
+                "py36-none-any",
+                "py3-none-any",
+                "py35-none-any",
+                "py34-none-any",
+                "py33-none-any",
+                "py32-none-any",
+                "py31-none-any",
+                "py30-none-any",
+            ]
+        );
+    }
+
+    #[test]
+    fn abi_flag_dropped_from_38() {
+        let target = TargetPython::parse("3.11", vec!["linux_x86_64".to_owned()]).unwrap();
+
+        assert_eq!(
+            target.supported_tags()[0],
+            Tag::new("cp311", "cp311", "linux_x86_64")
+        );
+    }
+
+    #[test]
+    fn reject_invalid_python_version() {
+        assert!(TargetPython::parse("three", vec![]).is_err());
+        assert!(TargetPython::parse("3", vec![]).is_err());
+        assert!(TargetPython::parse("3.6.x", vec![]).is_err());
+    }
+
+    #[test]
+    fn mac_platform_tags() {
+        let platforms = mac_platforms((12, 6), "aarch64");
+
+        assert_eq!(
+            &platforms[..5],
+            &[
+                "macosx_12_0_arm64",
+                "macosx_12_0_universal2",
+                "macosx_11_0_arm64",
+                "macosx_11_0_universal2",
+                "macosx_10_16_universal2",
+            ]
+        );
+        assert_eq!(platforms.last().unwrap(), "macosx_10_4_universal2");
+
+        let platforms = mac_platforms((10, 15), "x86_64");
+
+        assert_eq!(platforms[0], "macosx_10_15_x86_64");
+        assert!(platforms.contains(&"macosx_10_9_intel".to_owned()));
+        assert!(!platforms.iter().any(|x| x.starts_with("macosx_11")));
+    }
+}
@@ -0,0 +1,45 @@
+use std::fs;
+use std::path::Path;
+
+use installed::InstalledDistribution;
+use record::{format_record, RecordEntry};
+
+/// Writes `contents` to `path` under `dir`, creating any directories needed.
+pub fn write(dir: &Path, path: &str, contents: &str) {
+    let path = dir.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, contents).unwrap();
+}
+
+/// Installs `name` `version` into `site_packages` the way installing a wheel
+/// would, writing `files` and a RECORD listing them with their hashes. Paths
+/// are relative to `site_packages` and may climb out of it, eg.
+/// `../../../bin/demo`.
+pub fn install_distribution(
+    site_packages: &Path,
+    name: &str,
+    version: &str,
+    files: &[(&str, &str)],
+) -> InstalledDistribution {
+    let dist_info = format!("{}-{}.dist-info", name, version);
+    let metadata = format!("Name: {}\nVersion: {}\n", name, version);
+    let metadata_path = format!("{}/METADATA", dist_info);
+    let mut files = files.to_vec();
+    files.push((&metadata_path, &metadata));
+    let mut record: Vec<RecordEntry> = files
+        .iter()
+        .map(|&(path, contents)| {
+            write(site_packages, path, contents);
+            RecordEntry::new(path, contents.as_bytes())
+        })
+        .collect();
+    record.push(RecordEntry {
+        path: format!("{}/RECORD", dist_info),
+        hash: None,
+        size: None,
+    });
+    write(site_packages, &format!("{}/RECORD", dist_info), &format_record(&record));
+    InstalledDistribution::read(&site_packages.join(&dist_info))
+        .unwrap()
+        .unwrap()
+}
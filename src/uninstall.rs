@@ -0,0 +1,156 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use installed::{resolve_record_path, InstalledDistribution};
+use errors::*;
+
+/// Removes `distribution` from the environment at `prefix`: every file its
+/// RECORD lists, the bytecode python compiled from them, its metadata, and
+/// any directories in site-packages left empty. Nothing is removed unless
+/// every file is inside `prefix`. Returns the files that were removed.
+pub fn uninstall(distribution: &InstalledDistribution, prefix: &Path) -> Result<Vec<PathBuf>> {
+    let prefix = fs::canonicalize(prefix)?;
+    let root = fs::canonicalize(distribution.root())?;
+    let metadata_dir = root.join(distribution.path.file_name().unwrap_or_default());
+    let mut files = BTreeSet::new();
+    for entry in distribution.record()? {
+        let path = resolve_record_path(&root, &entry.path);
+        let path = match path.parent().map(fs::canonicalize) {
+            // Resolve symlinks such as lib64 -> lib before comparing
+            Some(Ok(parent)) => parent.join(path.file_name().unwrap_or_default()),
+            _ => path,
+        };
+        if !path.starts_with(&prefix) || path == prefix {
+            bail!(ErrorKind::UninstallOutsidePrefix(
+                distribution.name.to_owned(),
+                path
+            ));
+        }
+        if path.starts_with(&metadata_dir) {
+            continue;
+        }
+        if path.extension().map_or(false, |x| x == "py") {
+            files.extend(compiled_files(&path)?);
+        }
+        files.insert(path);
+    }
+
+    let mut removed = vec![];
+    for path in files.iter() {
+        match fs::remove_file(path) {
+            Ok(()) => removed.push(path.to_owned()),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => {
+                return Err(e).chain_err(|| format!("failed to remove {}", path.display()));
+            }
+        }
+    }
+    fs::remove_dir_all(&metadata_dir)?;
+    for path in files.iter() {
+        remove_empty_dirs(path, &root)?;
+    }
+    Ok(removed)
+}
+
+/// The `__pycache__` files python compiled from the module `path`, eg.
+/// `__pycache__/six.cpython-36.pyc` for `six.py`.
+fn compiled_files(path: &Path) -> Result<Vec<PathBuf>> {
+    let (dir, stem) = match (path.parent(), path.file_stem()) {
+        (Some(dir), Some(stem)) => (dir.join("__pycache__"), stem.to_string_lossy()),
+        _ => return Ok(vec![]),
+    };
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+    let prefix = format!("{}.", stem);
+    let mut files = vec![];
+    for entry in fs::read_dir(&dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(&prefix) && name.ends_with(".pyc") {
+            files.push(entry.path());
+        }
+    }
+    Ok(files)
+}
+
+/// Removes the directories above `path` that are now empty, stopping at
+/// `root`. Directories outside `root`, such as `bin`, are left alone.
+fn remove_empty_dirs(path: &Path, root: &Path) -> Result<()> {
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == root || !current.starts_with(root) {
+            break;
+        }
+        let is_empty = match fs::read_dir(current) {
+            Ok(mut entries) => entries.next().is_none(),
+            Err(_) => false,
+        };
+        if !is_empty {
+            break;
+        }
+        fs::remove_dir(current)?;
+        dir = current.parent();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use test_support::{install_distribution, write};
+    use uninstall::*;
+
+    fn install(prefix: &Path, files: &[(&str, &str)]) -> InstalledDistribution {
+        let site_packages = prefix.join("lib/python3.6/site-packages");
+        let distribution = install_distribution(&site_packages, "six", "1.11.0", files);
+        write(&site_packages, "six-1.11.0.dist-info/direct_url.json", "{\"url\": \"x\"}");
+        distribution
+    }
+
+    #[test]
+    fn uninstalls_recorded_files() {
+        let dir = TempDir::new("venv").unwrap();
+        let prefix = dir.path();
+        let distribution = install(
+            prefix,
+            &[("six.py", ""), ("sixlib/__init__.py", ""), ("sixlib/data/x.txt", ""),
+              ("../../../bin/six", "")],
+        );
+        let site_packages = prefix.join("lib/python3.6/site-packages");
+        for path in &["__pycache__/six.cpython-36.pyc", "__pycache__/other.pyc",
+                      "sixlib/__pycache__/__init__.cpython-36.pyc", "other.py"] {
+            write(&site_packages, path, "");
+        }
+        write(prefix, "bin/python", "");
+
+        let removed = uninstall(&distribution, prefix).unwrap();
+
+        assert_eq!(removed.len(), 6);
+        assert!(!site_packages.join("six-1.11.0.dist-info").exists());
+        assert!(!site_packages.join("sixlib").exists());
+        assert!(!site_packages.join("six.py").exists());
+        assert!(!site_packages.join("__pycache__/six.cpython-36.pyc").exists());
+        assert!(site_packages.join("__pycache__/other.pyc").exists());
+        assert!(site_packages.join("other.py").exists());
+        assert!(!prefix.join("bin/six").exists());
+        assert!(prefix.join("bin/python").exists());
+    }
+
+    #[test]
+    fn refuses_files_outside_prefix() {
+        let dir = TempDir::new("venv").unwrap();
+        let prefix = dir.path().join("venv");
+        let distribution = install(&prefix, &[("six.py", ""), ("../../../../outside.txt", "")]);
+
+        match *uninstall(&distribution, &prefix).unwrap_err().kind() {
+            ErrorKind::UninstallOutsidePrefix(ref name, _) => assert_eq!(name, "six"),
+            ref kind => panic!("unexpected error {:?}", kind),
+        }
+        assert!(dir.path().join("outside.txt").exists());
+        assert!(prefix.join("lib/python3.6/site-packages/six.py").exists());
+    }
+}
@@ -0,0 +1,424 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use base64;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+use interpreters::{short_version, InterpreterInfo};
+use install_wheel::InstallScheme;
+use errors::*;
+
+const ACTIVATE_SH: &str = include_str!("activate.sh");
+const ACTIVATE_FISH: &str = include_str!("activate.fish");
+
+/// A PEP 405 virtual environment, laid out the way `python -m venv` lays
+/// one out on posix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Virtualenv {
+    pub prefix: PathBuf,
+    /// The interpreter's full version as recorded in `pyvenv.cfg`.
+    pub version: String,
+}
+impl Virtualenv {
+    /// Creates a virtualenv at `prefix` for `interpreter`, or updates one that
+    /// is already there. Interpreters are linked in when `symlinks` is set,
+    /// copied otherwise. `prompt` is shown by the activation scripts.
+    pub fn create(
+        prefix: &Path,
+        interpreter: &InterpreterInfo,
+        prompt: &str,
+        symlinks: bool,
+    ) -> Result<Virtualenv> {
+        let is_empty_dir = |dir: &Path| fs::read_dir(dir).map(|mut x| x.next().is_none());
+        if prefix.exists() && !prefix.join("pyvenv.cfg").exists() && !is_empty_dir(prefix)? {
+            bail!(
+                "{} already exists and isn't a virtualenv",
+                prefix.display()
+            );
+        }
+        let virtualenv = Virtualenv {
+            prefix: prefix.to_owned(),
+            version: interpreter.version.clone(),
+        };
+        let short_version = short_version(&interpreter.version);
+        fs::create_dir_all(virtualenv.bin_dir())?;
+        fs::create_dir_all(prefix.join("include"))?;
+        fs::create_dir_all(virtualenv.site_packages())?;
+        if cfg!(all(target_pointer_width = "64", not(target_os = "macos")))
+            && fs::symlink_metadata(prefix.join("lib64")).is_err()
+        {
+            symlink(Path::new("lib"), &prefix.join("lib64"))?;
+        }
+
+        let home = interpreter
+            .executable
+            .parent()
+            .ok_or_else(|| format!("{} has no parent", interpreter.executable.display()))?;
+        fs::write(
+            prefix.join("pyvenv.cfg"),
+            format!(
+                "home = {}
+include-system-site-packages = false
+version = {}
+executable = {}
+prompt = {}
+",
+                home.display(),
+                interpreter.version,
+                interpreter.executable.display(),
+                prompt
+            ),
+        )?;
+
+        let python = virtualenv.python();
+        replace_file(&python)?;
+        if symlinks {
+            symlink(&interpreter.executable, &python)?;
+        } else {
+            fs::copy(&interpreter.executable, &python)?;
+        }
+        let major = short_version.split('.').next().unwrap_or("3");
+        for name in &[format!("python{}", major), format!("python{}", short_version)] {
+            let path = virtualenv.bin_dir().join(name);
+            replace_file(&path)?;
+            if symlinks {
+                symlink(Path::new("python"), &path)?;
+            } else {
+                fs::copy(&interpreter.executable, &path)?;
+            }
+        }
+
+        let prefix_string = prefix.to_string_lossy();
+        fs::write(
+            virtualenv.bin_dir().join("activate"),
+            ACTIVATE_SH
+                .replace("__VIRTUAL_ENV__", &sh_quote(&prefix_string))
+                .replace("__VIRTUAL_ENV_PROMPT__", &sh_quote(prompt)),
+        )?;
+        fs::write(
+            virtualenv.bin_dir().join("activate.fish"),
+            ACTIVATE_FISH
+                .replace("__VIRTUAL_ENV__", &fish_quote(&prefix_string))
+                .replace("__VIRTUAL_ENV_PROMPT__", &fish_quote(prompt)),
+        )?;
+        Ok(virtualenv)
+    }
+
+    /// The virtualenv at `prefix`, failing if there isn't one.
+    pub fn open(prefix: &Path) -> Result<Virtualenv> {
+        let config = fs::read_to_string(prefix.join("pyvenv.cfg"))
+            .chain_err(|| format!("{} isn't a virtualenv", prefix.display()))?;
+        let version = config
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(key), Some(value)) => Some((key.trim(), value.trim())),
+                    _ => None,
+                }
+            })
+            .find(|&(key, _)| key == "version" || key == "version_info")
+            .map(|(_, value)| value.to_owned())
+            .ok_or_else(|| format!("{}/pyvenv.cfg has no version", prefix.display()))?;
+        Ok(Virtualenv {
+            prefix: prefix.to_owned(),
+            version: version,
+        })
+    }
+
+    pub fn bin_dir(&self) -> PathBuf {
+        self.prefix.join("bin")
+    }
+
+    pub fn python(&self) -> PathBuf {
+        self.bin_dir().join("python")
+    }
+
+    pub fn site_packages(&self) -> PathBuf {
+        self.install_scheme().purelib
+    }
+
+    /// Where wheels installed into this virtualenv go.
+    pub fn install_scheme(&self) -> InstallScheme {
+        InstallScheme::for_prefix(&self.prefix, &short_version(&self.version))
+    }
+}
+
+/// The environment variables pipenv reads to decide where a project's
+/// virtualenv lives.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VirtualenvSettings {
+    /// `VIRTUAL_ENV`, the virtualenv the user activated, unless it's one
+    /// pipenv activated (`PIPENV_ACTIVE`) or `PIPENV_IGNORE_VIRTUALENVS` is
+    /// set. It's used instead of the project's own.
+    pub active_virtualenv: Option<PathBuf>,
+    /// `PIPENV_VENV_IN_PROJECT`: `Some(false)` rules out a `.venv` directory
+    /// even when the project has one.
+    pub venv_in_project: Option<bool>,
+    /// `PIPENV_CUSTOM_VENV_NAME`
+    pub custom_name: Option<String>,
+    /// `WORKON_HOME`, defaulting to `$XDG_DATA_HOME/virtualenvs`.
+    pub workon_home: PathBuf,
+}
+impl VirtualenvSettings {
+    pub fn from_env() -> Result<VirtualenvSettings> {
+        let non_empty = |name: &str| env::var_os(name).filter(|x| !x.is_empty());
+        let home = || {
+            env::var_os("HOME")
+                .map(PathBuf::from)
+                .ok_or("HOME isn't set")
+        };
+        let workon_home = match non_empty("WORKON_HOME") {
+            Some(workon_home) => expand_home(workon_home, &home()?),
+            None => match non_empty("XDG_DATA_HOME") {
+                Some(data_home) => PathBuf::from(data_home).join("virtualenvs"),
+                None => home()?.join(".local/share/virtualenvs"),
+            },
+        };
+        let ignore_virtualenvs = env::var_os("PIPENV_ACTIVE").is_some()
+            || non_empty("PIPENV_IGNORE_VIRTUALENVS")
+                .map_or(false, |x| env_bool(&x.to_string_lossy()) == Some(true));
+        Ok(VirtualenvSettings {
+            active_virtualenv: non_empty("VIRTUAL_ENV")
+                .filter(|_| !ignore_virtualenvs)
+                .map(PathBuf::from),
+            venv_in_project: non_empty("PIPENV_VENV_IN_PROJECT")
+                .and_then(|x| env_bool(&x.to_string_lossy())),
+            custom_name: non_empty("PIPENV_CUSTOM_VENV_NAME")
+                .map(|x| x.to_string_lossy().into_owned()),
+            workon_home: workon_home,
+        })
+    }
+}
+
+fn expand_home(path: OsString, home: &Path) -> PathBuf {
+    let path = PathBuf::from(path);
+    match path.strip_prefix("~") {
+        Ok(rest) => home.join(rest),
+        Err(_) => path,
+    }
+}
+
+/// Reads a boolean environment variable the way pipenv does.
+fn env_bool(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// The name pipenv gives a project's virtualenv under `WORKON_HOME`: the
+/// project directory's name, made shell safe, followed by a hash of the
+/// Pipfile's path, eg. `myproject-d987xlMg`.
+pub fn virtualenv_name(pipfile_path: &Path) -> String {
+    lazy_static! {
+        static ref UNSAFE_RE: Regex = Regex::new(r#"[ &$`!*@"()\[\]\\\r\n\t]"#).unwrap();
+    }
+    let project_name = pipfile_path
+        .parent()
+        .and_then(|x| x.file_name())
+        .map_or(String::new(), |x| x.to_string_lossy().into_owned());
+    // Shebangs are limited to 127 bytes, which the interpreter path inside
+    // the virtualenv has to fit in
+    let sanitized: String = UNSAFE_RE
+        .replace_all(&project_name, "_")
+        .chars()
+        .take(42)
+        .collect();
+    let digest = Sha256::digest(pipfile_path.to_string_lossy().as_bytes());
+    let hash = base64::encode_config(&digest[..6], base64::URL_SAFE);
+    format!("{}-{}", sanitized, hash)
+}
+
+/// Where pipenv would put the virtualenv of the project in `project_dir`, so
+/// the two tools share it. An activated virtualenv wins, then a `.venv`
+/// directory in the project, and a `.venv` file names a virtualenv either by
+/// path or by name under `WORKON_HOME`.
+pub fn virtualenv_location(project_dir: &Path, settings: &VirtualenvSettings) -> Result<PathBuf> {
+    let project_dir = fs::canonicalize(project_dir)
+        .chain_err(|| format!("can't find project directory {}", project_dir.display()))?;
+    let in_workon_home = || match settings.custom_name {
+        Some(ref name) => settings.workon_home.join(name),
+        None => settings
+            .workon_home
+            .join(virtualenv_name(&project_dir.join("Pipfile"))),
+    };
+    if let Some(ref active_virtualenv) = settings.active_virtualenv {
+        return Ok(active_virtualenv.to_owned());
+    }
+    let dot_venv = project_dir.join(".venv");
+    let in_project = match settings.venv_in_project {
+        Some(false) => false,
+        Some(true) => dot_venv.is_dir() || !dot_venv.exists(),
+        None => dot_venv.is_dir(),
+    };
+    if in_project {
+        return Ok(dot_venv);
+    }
+    if !dot_venv.is_file() {
+        return Ok(in_workon_home());
+    }
+    let name = fs::read_to_string(&dot_venv)?.trim().to_owned();
+    if name == "" {
+        Ok(in_workon_home())
+    } else if name.contains('/') {
+        Ok(project_dir.join(name))
+    } else {
+        Ok(settings.workon_home.join(name))
+    }
+}
+
+/// Removes `path` if it exists, so it can be recreated as a link or copy.
+fn replace_file(path: &Path) -> Result<()> {
+    if fs::symlink_metadata(path).is_ok() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> Result<()> {
+    ::std::os::unix::fs::symlink(target, link)
+        .chain_err(|| format!("failed to link {} to {}", link.display(), target.display()))
+}
+
+#[cfg(not(unix))]
+fn symlink(target: &Path, link: &Path) -> Result<()> {
+    fs::copy(target, link)?;
+    Ok(())
+}
+
+/// `value` as a single quoted sh word.
+fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// `value` as a single quoted fish word.
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+    use interpreters::MarkerEnvironment;
+    use venv::*;
+
+    fn settings(workon_home: &Path) -> VirtualenvSettings {
+        VirtualenvSettings {
+            active_virtualenv: None,
+            venv_in_project: None,
+            custom_name: None,
+            workon_home: workon_home.to_owned(),
+        }
+    }
+
+    #[test]
+    fn pipenv_virtualenv_names() {
+        assert_eq!(
+            virtualenv_name(Path::new("/home/jacob/src/my project/Pipfile")),
+            "my_project-d987xlMg"
+        );
+        assert_eq!(
+            virtualenv_name(Path::new(&format!("/src/{}/Pipfile", "x".repeat(50)))).len(),
+            42 + 1 + 8
+        );
+    }
+
+    #[test]
+    fn pipenv_virtualenv_locations() {
+        let dir = TempDir::new("venv").unwrap();
+        let project = fs::canonicalize(dir.path()).unwrap().join("project");
+        fs::create_dir(&project).unwrap();
+        let workon_home = Path::new("/virtualenvs");
+        let mut settings = settings(workon_home);
+
+        assert_eq!(
+            virtualenv_location(&project, &settings).unwrap(),
+            workon_home.join(virtualenv_name(&project.join("Pipfile")))
+        );
+        settings.custom_name = Some("custom".to_owned());
+        assert_eq!(
+            virtualenv_location(&project, &settings).unwrap(),
+            workon_home.join("custom")
+        );
+        settings.venv_in_project = Some(true);
+        assert_eq!(
+            virtualenv_location(&project, &settings).unwrap(),
+            project.join(".venv")
+        );
+
+        fs::write(project.join(".venv"), "shared\n").unwrap();
+        assert_eq!(
+            virtualenv_location(&project, &settings).unwrap(),
+            workon_home.join("shared")
+        );
+        fs::write(project.join(".venv"), "../envs/project").unwrap();
+        assert_eq!(
+            virtualenv_location(&project, &settings).unwrap(),
+            project.join("../envs/project")
+        );
+        settings.venv_in_project = Some(false);
+        assert_eq!(
+            virtualenv_location(&project, &settings).unwrap(),
+            project.join("../envs/project")
+        );
+
+        fs::remove_file(project.join(".venv")).unwrap();
+        fs::create_dir(project.join(".venv")).unwrap();
+        assert_eq!(
+            virtualenv_location(&project, &settings).unwrap(),
+            workon_home.join("custom")
+        );
+        settings.venv_in_project = None;
+        assert_eq!(
+            virtualenv_location(&project, &settings).unwrap(),
+            project.join(".venv")
+        );
+        settings.active_virtualenv = Some(PathBuf::from("/home/me/.venvs/active"));
+        assert_eq!(
+            virtualenv_location(&project, &settings).unwrap(),
+            Path::new("/home/me/.venvs/active")
+        );
+    }
+
+    #[test]
+    fn create_and_open() {
+        let dir = TempDir::new("venv").unwrap();
+        let prefix = dir.path().join("my env");
+        let base_python = dir.path().join("python3.6");
+        fs::write(&base_python, "").unwrap();
+        let interpreter = InterpreterInfo {
+            executable: base_python.clone(),
+            version: "3.6.3".to_owned(),
+            implementation: "cpython".to_owned(),
+            markers: MarkerEnvironment::default(),
+        };
+
+        let virtualenv = Virtualenv::create(&prefix, &interpreter, "it's mine", true).unwrap();
+
+        assert_eq!(
+            virtualenv.site_packages(),
+            prefix.join("lib/python3.6/site-packages")
+        );
+        assert!(virtualenv.site_packages().is_dir());
+        assert_eq!(fs::read_link(virtualenv.python()).unwrap(), base_python);
+        assert_eq!(
+            fs::read_link(prefix.join("bin/python3.6")).unwrap(),
+            Path::new("python")
+        );
+        let activate = fs::read_to_string(prefix.join("bin/activate")).unwrap();
+        assert!(activate.contains(&format!("VIRTUAL_ENV='{}'\n", prefix.display())));
+        assert!(activate.contains("VIRTUAL_ENV_PROMPT='it'\\''s mine'\n"));
+        let activate_fish = fs::read_to_string(prefix.join("bin/activate.fish")).unwrap();
+        assert!(activate_fish.contains("set -gx VIRTUAL_ENV_PROMPT 'it\\'s mine'\n"));
+        assert_eq!(Virtualenv::open(&prefix).unwrap(), virtualenv);
+
+        // Recreating an existing virtualenv updates it in place
+        assert!(Virtualenv::create(&prefix, &interpreter, "mine", true).is_ok());
+        assert!(Virtualenv::create(dir.path(), &interpreter, "mine", true).is_err());
+    }
+}
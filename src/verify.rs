@@ -0,0 +1,405 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use pipfile::{lockfile_category, pipfile_hash, LockedPackage, Lockfile, PackageMap, Pipfile};
+use pypi::canonical_name;
+use semver_utils::normalize_and_parse_version_string;
+use version_req::PackageVersionReq;
+use errors::*;
+
+/// A reason a Pipfile.lock can't be trusted to reflect its Pipfile.
+#[derive(Debug, PartialEq)]
+pub enum LockProblem {
+    PipfileHashChanged {
+        locked: String,
+        current: String,
+    },
+    PythonVersionMismatch {
+        key: String,
+        required: String,
+        found: String,
+    },
+    NotLocked {
+        section: String,
+        name: String,
+    },
+    UnsatisfiedSpecifier {
+        section: String,
+        name: String,
+        locked: String,
+        specifier: String,
+    },
+    NotRequired {
+        section: String,
+        name: String,
+    },
+}
+impl fmt::Display for LockProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LockProblem::PipfileHashChanged {
+                ref locked,
+                ref current,
+            } => write!(
+                f,
+                "Pipfile hash is {} but Pipfile.lock was generated from {}",
+                current, locked
+            ),
+            LockProblem::PythonVersionMismatch {
+                ref key,
+                ref required,
+                ref found,
+            } => write!(f, "{} {} is required but found {}", key, required, found),
+            LockProblem::NotLocked {
+                ref section,
+                ref name,
+            } => write!(f, "[{}] {} is not in Pipfile.lock", section, name),
+            LockProblem::UnsatisfiedSpecifier {
+                ref section,
+                ref name,
+                ref locked,
+                ref specifier,
+            } => write!(
+                f,
+                "[{}] {} is locked to {} which doesn't satisfy `{}`",
+                section, name, locked, specifier
+            ),
+            LockProblem::NotRequired {
+                ref section,
+                ref name,
+            } => write!(
+                f,
+                "[{}] {} is in Pipfile.lock but nothing in the Pipfile requires it",
+                section, name
+            ),
+        }
+    }
+}
+
+fn section_problems(
+    section: &str,
+    packages: &PackageMap,
+    locked: &BTreeMap<String, LockedPackage>,
+) -> Result<Vec<LockProblem>> {
+    let mut problems = vec![];
+    for (name, package_info) in packages.iter() {
+        let locked_package = match locked.get(&canonical_name(name)) {
+            Some(locked_package) => locked_package,
+            None => {
+                problems.push(LockProblem::NotLocked {
+                    section: section.to_owned(),
+                    name: name.to_owned(),
+                });
+                continue;
+            }
+        };
+        let (specifier, locked_version) =
+            match (package_info.version_spec(), locked_package.version.as_ref()) {
+                (Some(specifier), Some(locked_version)) => (specifier, locked_version),
+                _ => continue,
+            };
+        let version = normalize_and_parse_version_string(locked_version.trim_left_matches("=="))?;
+        // The lock may legitimately hold a pre-release, eg. when nothing else
+        // satisfied the specifier, so only the specifier itself is checked
+        if !PackageVersionReq::from_specifier(name, specifier)?
+            .matches_with_prereleases(&version, true)
+        {
+            problems.push(LockProblem::UnsatisfiedSpecifier {
+                section: section.to_owned(),
+                name: name.to_owned(),
+                locked: locked_version.to_owned(),
+                specifier: specifier.to_owned(),
+            });
+        }
+    }
+    Ok(problems)
+}
+
+/// The packages of `locked` that neither the Pipfile `packages` nor their
+/// locked dependencies require, eg. ones removed from the Pipfile since it was
+/// locked. `requires` gives the requirements of a package at a version.
+fn unrequired_problems<F>(
+    section: &str,
+    packages: &PackageMap,
+    locked: &BTreeMap<String, LockedPackage>,
+    requires: &mut F,
+) -> Result<Vec<LockProblem>>
+where
+    F: FnMut(&str, &str) -> Result<Vec<PackageVersionReq>>,
+{
+    let mut required = BTreeSet::new();
+    let mut pending: Vec<String> = packages.keys().map(|name| canonical_name(name)).collect();
+    while let Some(name) = pending.pop() {
+        if !required.insert(name.clone()) {
+            continue;
+        }
+        let version = match locked.get(&name).and_then(|x| x.version.as_ref()) {
+            Some(version) => version.trim_left_matches("=="),
+            None => continue,
+        };
+        // Markers and extras aren't evaluated, so anything that might be
+        // required counts as required
+        for req in requires(&name, version)? {
+            pending.push(canonical_name(req.name()));
+        }
+    }
+    Ok(locked
+        .keys()
+        .filter(|name| !required.contains(*name))
+        .map(|name| LockProblem::NotRequired {
+            section: section.to_owned(),
+            name: name.to_owned(),
+        })
+        .collect())
+}
+
+fn python_version_problems(lockfile: &Lockfile, python_full_version: &str) -> Vec<LockProblem> {
+    let python_version = python_full_version
+        .splitn(3, '.')
+        .take(2)
+        .collect::<Vec<_>>()
+        .join(".");
+    let requires = &lockfile.meta.requires;
+    let checks = [
+        ("python_version", &requires.python_version, python_version),
+        ("python_full_version", &requires.python_full_version, python_full_version.to_owned()),
+    ];
+    let mut problems = vec![];
+    for &(key, required, ref found) in checks.iter() {
+        if let Some(ref required) = *required {
+            if required != found {
+                problems.push(LockProblem::PythonVersionMismatch {
+                    key: key.to_owned(),
+                    required: required.to_owned(),
+                    found: found.to_owned(),
+                });
+            }
+        }
+    }
+    problems
+}
+
+/// Everything that makes `lockfile` unsuitable for a deployment of `pipfile`.
+/// `python_full_version` is the version of the interpreter being deployed to.
+pub fn lock_problems(
+    pipfile_bytes: &[u8],
+    pipfile: &Pipfile,
+    lockfile: &Lockfile,
+    python_full_version: Option<&str>,
+) -> Result<Vec<LockProblem>> {
+    let mut problems = vec![];
+    let current = pipfile_hash(pipfile_bytes)?;
+    if current != lockfile.meta.hash.sha256 {
+        problems.push(LockProblem::PipfileHashChanged {
+            locked: lockfile.meta.hash.sha256.to_owned(),
+            current: current,
+        });
+    }
+    if let Some(python_full_version) = python_full_version {
+        problems.extend(python_version_problems(lockfile, python_full_version));
+    }
+    // Anything removed from the Pipfile since also changed its hash
+    let not_locked = BTreeMap::new();
+    for category in pipfile.category_names() {
+        problems.extend(section_problems(
+            &category,
+            pipfile.category(&category),
+            lockfile.category(lockfile_category(&category)).unwrap_or(&not_locked),
+        )?);
+    }
+    Ok(problems)
+}
+
+/// The packages of the Pipfile `categories` that `lockfile` doesn't lock, or
+/// locks to versions their specifiers no longer allow, and the packages it
+/// locks for them that nothing in the Pipfile requires any more. `requires`
+/// gives the requirements of a package at a version.
+pub fn category_problems<F>(
+    pipfile: &Pipfile,
+    lockfile: Option<&Lockfile>,
+    categories: &[String],
+    mut requires: F,
+) -> Result<Vec<LockProblem>>
+where
+    F: FnMut(&str, &str) -> Result<Vec<PackageVersionReq>>,
+{
+    let mut problems = vec![];
+    let not_locked = BTreeMap::new();
+    for category in categories.iter() {
+        let packages = pipfile.category(category);
+        let locked = lockfile
+            .and_then(|lockfile| lockfile.category(lockfile_category(category)))
+            .unwrap_or(&not_locked);
+        problems.extend(section_problems(category, packages, locked)?);
+        problems.extend(unrequired_problems(category, packages, locked, &mut requires)?);
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use toml;
+    use pipfile::*;
+    use version_req::PackageVersionReq;
+    use verify::*;
+    use errors::*;
+
+    fn make_lockfile(requests_version: &str) -> Lockfile {
+        let mut default = BTreeMap::new();
+        default.insert(
+            "requests".to_owned(),
+            LockedPackage {
+                version: Some(requests_version.to_owned()),
+                ..Default::default()
+            },
+        );
+        let requires = Requires {
+            python_version: Some("3.6".to_owned()),
+            ..Default::default()
+        };
+        let mut categories = BTreeMap::new();
+        categories.insert("default".to_owned(), default);
+        Lockfile {
+            categories: categories,
+            meta: LockfileMeta {
+                hash: LockfileMetaHash {
+                    sha256: "stale".to_owned(),
+                },
+                pipfile_spec: None,
+                requires: requires,
+                sources: vec![],
+            },
+        }
+    }
+
+    fn make_pipfile(packages: &[(&str, &str)]) -> Pipfile {
+        let mut categories = BTreeMap::new();
+        categories.insert(
+            "packages".to_owned(),
+            packages
+                .iter()
+                .map(|&(name, spec)| {
+                    let value = toml::Value::String(spec.to_owned());
+                    (name.to_owned(), PackageInfo::from_toml(name, &value).unwrap())
+                })
+                .collect(),
+        );
+        Pipfile {
+            source: vec![],
+            requires: Requires::default(),
+            categories: categories,
+            pipenv: PipenvSettings::default(),
+            scripts: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let pipfile = make_pipfile(&[("Requests", ">=2.19"), ("django", "*")]);
+
+        let problems =
+            lock_problems(b"", &pipfile, &make_lockfile("==2.18.4"), Some("3.7.1")).unwrap();
+
+        assert_eq!(problems.len(), 4);
+        assert_eq!(
+            problems[1],
+            LockProblem::PythonVersionMismatch {
+                key: "python_version".to_owned(),
+                required: "3.6".to_owned(),
+                found: "3.7".to_owned(),
+            }
+        );
+        assert!(problems.contains(&LockProblem::NotLocked {
+            section: "packages".to_owned(),
+            name: "django".to_owned(),
+        }));
+        assert!(problems.contains(&LockProblem::UnsatisfiedSpecifier {
+            section: "packages".to_owned(),
+            name: "Requests".to_owned(),
+            locked: "==2.18.4".to_owned(),
+            specifier: ">=2.19".to_owned(),
+        }));
+    }
+
+    #[test]
+    fn up_to_date_lock_has_no_problems() {
+        let pipfile = make_pipfile(&[("requests", "~=2.18")]);
+        let mut lockfile = make_lockfile("==2.18.4");
+        lockfile.meta.hash.sha256 = pipfile_hash(b"").unwrap();
+
+        assert_eq!(
+            lock_problems(b"", &pipfile, &lockfile, Some("3.6.3")).unwrap(),
+            vec![]
+        );
+    }
+    fn no_requires(_: &str, _: &str) -> Result<Vec<PackageVersionReq>> {
+        Ok(vec![])
+    }
+
+    #[test]
+    fn only_the_given_categories_are_checked() {
+        let pipfile = make_pipfile(&[("requests", ">=2.19")]);
+        let lockfile = make_lockfile("==2.18.4");
+        let dev = vec!["dev-packages".to_owned()];
+        let packages = vec!["packages".to_owned()];
+
+        assert_eq!(
+            category_problems(&pipfile, Some(&lockfile), &dev, no_requires).unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            category_problems(&pipfile, Some(&lockfile), &packages, no_requires)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            category_problems(&pipfile, None, &packages, no_requires).unwrap(),
+            vec![
+                LockProblem::NotLocked {
+                    section: "packages".to_owned(),
+                    name: "requests".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_packages_nothing_requires() {
+        let pipfile = make_pipfile(&[("requests", "*")]);
+        let mut lockfile = make_lockfile("==2.18.4");
+        for name in ["idna", "removed", "removed-dependency"].iter() {
+            lockfile.categories.get_mut("default").unwrap().insert(
+                name.to_string(),
+                LockedPackage {
+                    version: Some("==1.0".to_owned()),
+                    ..Default::default()
+                },
+            );
+        }
+        let requires = |name: &str, version: &str| {
+            let dependency = match (name, version) {
+                ("requests", "2.18.4") => "idna",
+                ("removed", "1.0") => "removed-dependency",
+                _ => return Ok(vec![]),
+            };
+            Ok(vec![PackageVersionReq::from_specifier(dependency, "*")?])
+        };
+        let packages = vec!["packages".to_owned()];
+
+        assert_eq!(
+            category_problems(&pipfile, Some(&lockfile), &packages, requires).unwrap(),
+            vec![
+                LockProblem::NotRequired {
+                    section: "packages".to_owned(),
+                    name: "removed".to_owned(),
+                },
+                LockProblem::NotRequired {
+                    section: "packages".to_owned(),
+                    name: "removed-dependency".to_owned(),
+                },
+            ]
+        );
+    }
+}
@@ -3,36 +3,205 @@ use std::fmt;
 use semver;
 
 use errors::*;
+use semver_utils::{is_prerelease, normalize_and_parse_version_string};
 
-#[derive(PartialEq)]
+enum Specifier {
+    Req(semver::VersionReq),
+    Pin(semver::Version),
+    Exclude(semver::Version),
+    ExcludeWildcard(semver::VersionReq),
+}
+
+fn parse_version_req(op: &str, version: &str) -> Result<semver::VersionReq> {
+    match semver::VersionReq::parse(&format!("{}{}", op, version)) {
+        Ok(req) => Ok(req),
+        Err(_) => {
+            let version = normalize_and_parse_version_string(version)?;
+            Ok(semver::VersionReq::parse(&format!("{}{}", op, version))?)
+        }
+    }
+}
+
+/// Whether `version` satisfies `req`, without semver's rule that pre-releases
+/// only match comparators on the same `major.minor.patch`. Adding a comparator
+/// `version` trivially satisfies lifts that restriction. semver treats `>=1.11`
+/// as matching all of `1.11`, including pre-releases such as `1.11rc1` which
+/// PEP 440 orders before it, so lower bounds are spelled out in full first.
+fn matches_including_prereleases(req: &semver::VersionReq, version: &semver::Version) -> bool {
+    if !version.is_prerelease() || *req == semver::VersionReq::any() {
+        return req.matches(version);
+    }
+    let comparators: Vec<String> = req.to_string()
+        .split(", ")
+        .map(|comparator| {
+            let bound = comparator.trim_left_matches(">=").trim();
+            match normalize_and_parse_version_string(bound) {
+                Ok(bound) if comparator.starts_with(">=") => format!(">={}", bound),
+                _ => comparator.to_owned(),
+            }
+        })
+        .collect();
+    semver::VersionReq::parse(&format!("{}, >={}", comparators.join(", "), version))
+        .map(|req| req.matches(version))
+        .unwrap_or(false)
+}
+
+/// Translates a single PEP 440 clause (`~=2.3`, `==1.*`, `<2`, ...) into the
+/// closest semver equivalent, along with whether the clause mentions a
+/// pre-release.
+fn parse_specifier_clause(clause: &str) -> Result<(Specifier, bool)> {
+    lazy_static! {
+        static ref CLAUSE_RE: Regex = Regex::new(r"^(~=|===|==|!=|<=|>=|<|>)\s*(\S+)$").unwrap();
+    }
+    let captures = CLAUSE_RE
+        .captures(clause)
+        .ok_or_else(|| ErrorKind::InvalidVersionSpecifier(clause.to_owned()))?;
+    let version = &captures[2];
+    let mentions_prerelease = &captures[1] != "!="
+        && normalize_and_parse_version_string(version.trim_right_matches(".*"))
+            .map(|version| is_prerelease(&version))
+            .unwrap_or(false);
+    let specifier = match &captures[1] {
+        "==" | "===" if version.ends_with(".*") => {
+            Specifier::Req(semver::VersionReq::parse(version)?)
+        }
+        "==" | "===" => Specifier::Pin(normalize_and_parse_version_string(version)?),
+        "!=" if version.ends_with(".*") => {
+            Specifier::ExcludeWildcard(semver::VersionReq::parse(version)?)
+        }
+        "!=" => Specifier::Exclude(normalize_and_parse_version_string(version)?),
+        "~=" => {
+            let lower = normalize_and_parse_version_string(version)?;
+            let mut upper = lower.clone();
+            match version.split('.').count() {
+                0 | 1 => bail!(ErrorKind::InvalidVersionSpecifier(clause.to_owned())),
+                2 => upper.increment_major(),
+                3 => upper.increment_minor(),
+                _ => upper.increment_patch(),
+            }
+            Specifier::Req(semver::VersionReq::parse(
+                &format!(">={}, <{}", lower, upper),
+            )?)
+        }
+        op => Specifier::Req(parse_version_req(op, version)?),
+    };
+    Ok((specifier, mentions_prerelease))
+}
+
+#[derive(PartialEq, Clone)]
 pub struct PackageVersionReq {
     package_name: String,
     version_reqs: Vec<semver::VersionReq>,
+    excluded_versions: Vec<semver::Version>,
+    excluded_wildcards: Vec<semver::VersionReq>,
+    pinned_version: Option<semver::Version>,
+    mentions_prerelease: bool,
+    extras: Vec<String>,
+    markers: Option<String>,
 }
 impl PackageVersionReq {
     pub fn new(package_name: String, version_reqs: Vec<semver::VersionReq>) -> PackageVersionReq {
         PackageVersionReq {
             package_name: package_name,
             version_reqs: version_reqs,
+            excluded_versions: vec![],
+            excluded_wildcards: vec![],
+            pinned_version: None,
+            mentions_prerelease: false,
+            extras: vec![],
+            markers: None,
         }
     }
     pub fn parse_requirement(req_str: &str) -> Result<PackageVersionReq> {
         lazy_static! {
-            static ref PACKAGE_NAME_RE: Regex = Regex::new(r"\w+").unwrap();
-            static ref VERSION_REQ_RE: Regex = Regex::new(r"[<=>]{1,2}\d+(\.\d+){0,2}").unwrap();
+            static ref REQUIREMENT_RE: Regex = Regex::new(
+                r"^\s*([A-Za-z0-9][A-Za-z0-9._-]*)\s*(\[([^\]]*)\])?\s*\(?([^;()]*)\)?\s*(;\s*(.*))?$"
+            ).unwrap();
+        }
+        let captures = REQUIREMENT_RE
+            .captures(req_str)
+            .ok_or_else(|| ErrorKind::PackageNameRegexFailed(req_str.to_owned()))?;
+        let mut req = Self::from_specifier(
+            &captures[1],
+            captures.get(4).map(|x| x.as_str()).unwrap_or(""),
+        )?;
+        if let Some(extras) = captures.get(3) {
+            req.extras = extras
+                .as_str()
+                .split(',')
+                .map(|x| x.trim().to_owned())
+                .filter(|x| x != "")
+                .collect();
+        }
+        req.markers = captures
+            .get(6)
+            .map(|x| x.as_str().trim().to_owned())
+            .filter(|x| x != "");
+        Ok(req)
+    }
+    /// Builds a requirement from a package name and a PEP 440 specifier set
+    /// such as `>=1.0,!=1.3,<2` or `*`, as found in a Pipfile.
+    pub fn from_specifier(package_name: &str, specifier: &str) -> Result<PackageVersionReq> {
+        let mut req = Self::new(package_name.to_owned(), vec![]);
+        for clause in specifier.split(',').map(|x| x.trim()) {
+            if clause == "" || clause == "*" {
+                continue;
+            }
+            let (specifier, mentions_prerelease) = parse_specifier_clause(clause)?;
+            req.mentions_prerelease |= mentions_prerelease;
+            match specifier {
+                Specifier::Req(version_req) => req.version_reqs.push(version_req),
+                Specifier::Pin(version) => {
+                    req.version_reqs.push(semver::VersionReq::exact(&version));
+                    req.pinned_version = Some(version);
+                }
+                Specifier::Exclude(version) => req.excluded_versions.push(version),
+                Specifier::ExcludeWildcard(wildcard) => req.excluded_wildcards.push(wildcard),
+            }
+        }
+        Ok(req)
+    }
+    pub fn with_extras(mut self, extras: Vec<String>) -> PackageVersionReq {
+        self.extras = extras;
+        self
+    }
+    pub fn with_markers(mut self, markers: Option<String>) -> PackageVersionReq {
+        self.markers = markers;
+        self
+    }
+    pub fn name(&self) -> &str {
+        &self.package_name
+    }
+    pub fn extras(&self) -> &[String] {
+        &self.extras
+    }
+    pub fn markers(&self) -> Option<&str> {
+        self.markers.as_ref().map(|x| x.as_str())
+    }
+    /// The version this requirement pins with `==`, if any.
+    pub fn pinned_version(&self) -> Option<&semver::Version> {
+        self.pinned_version.as_ref()
+    }
+    pub fn matches(&self, version: &semver::Version) -> bool {
+        self.matches_with_prereleases(version, false)
+    }
+    /// Follows PEP 440: a pre-release only matches when `allow_prereleases`
+    /// or when one of the clauses mentions a pre-release itself.
+    pub fn matches_with_prereleases(
+        &self,
+        version: &semver::Version,
+        allow_prereleases: bool,
+    ) -> bool {
+        if is_prerelease(version) && !allow_prereleases && !self.mentions_prerelease {
+            return false;
         }
-        let package_name = PACKAGE_NAME_RE
-            .find(req_str)
-            .ok_or_else(|| ErrorKind::PackageNameRegexFailed(req_str.to_owned()))?
-            .as_str()
-            .to_owned();
-        let version_reqs: Result<Vec<semver::VersionReq>> = VERSION_REQ_RE
-            .find_iter(req_str)
-            .map(|x| {
-                semver::VersionReq::parse(x.as_str()).map_err(|e| e.into())
-            })
-            .collect();
-        Ok(Self::new(package_name, version_reqs?))
+        self.version_reqs
+            .iter()
+            .all(|req| matches_including_prereleases(req, version))
+            && !self.excluded_versions.contains(version)
+            && !self.excluded_wildcards
+                .iter()
+                .any(|req| matches_including_prereleases(req, version))
     }
 }
 impl fmt::Debug for PackageVersionReq {
@@ -44,6 +213,8 @@ impl fmt::Debug for PackageVersionReq {
             self.version_reqs
                 .iter()
                 .map(|x| x.to_string())
+                .chain(self.excluded_versions.iter().map(|x| format!("!= {}", x)))
+                .chain(self.excluded_wildcards.iter().map(|x| format!("!= {}", x)))
                 .collect::<Vec<String>>()
                 .join(", ")
         )
@@ -84,6 +255,73 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_dotted_name_with_extras_and_markers() {
+        let requires_txt_line = "zope.interface[docs, test] (>=4.0) ; python_version >= '3'";
+
+        let version_req = PackageVersionReq::parse_requirement(requires_txt_line).unwrap();
+
+        assert_eq!(version_req.name(), "zope.interface");
+        assert_eq!(version_req.extras(), &["docs".to_owned(), "test".to_owned()]);
+        assert_eq!(version_req.markers(), Some("python_version >= '3'"));
+        assert!(version_req.matches(&semver::Version::parse("4.1.0").unwrap()));
+        assert!(!version_req.matches(&semver::Version::parse("3.9.0").unwrap()));
+    }
+
+    #[test]
+    fn parse_compatible_release_and_exclusion() {
+        let version_req = PackageVersionReq::from_specifier("requests", "~=2.18, !=2.19.0").unwrap();
+
+        assert!(version_req.matches(&semver::Version::parse("2.18.4").unwrap()));
+        assert!(!version_req.matches(&semver::Version::parse("2.19.0").unwrap()));
+        assert!(version_req.matches(&semver::Version::parse("2.20.1").unwrap()));
+        assert!(!version_req.matches(&semver::Version::parse("3.0.0").unwrap()));
+    }
+
+    #[test]
+    fn parse_wildcard_exclusion() {
+        let version_req =
+            PackageVersionReq::from_specifier("python", ">=2.7, !=3.0.*, !=3.1.*").unwrap();
+
+        assert!(version_req.matches(&semver::Version::parse("2.7.18").unwrap()));
+        assert!(!version_req.matches(&semver::Version::parse("3.1.4").unwrap()));
+        assert!(version_req.matches(&semver::Version::parse("3.6.0").unwrap()));
+    }
+
+    #[test]
+    fn prereleases_need_opting_in() {
+        let version_req = PackageVersionReq::from_specifier("django", ">=1.11").unwrap();
+        let prerelease = semver::Version::parse("2.0.0-b.1").unwrap();
+
+        assert!(!version_req.matches(&prerelease));
+        assert!(version_req.matches_with_prereleases(&prerelease, true));
+        assert!(!version_req.matches_with_prereleases(
+            &semver::Version::parse("1.11.0-rc.1").unwrap(),
+            true
+        ));
+    }
+
+    #[test]
+    fn prerelease_in_specifier_allows_prereleases() {
+        let version_req = PackageVersionReq::from_specifier("django", ">=2.0b1").unwrap();
+
+        assert!(version_req.matches(&semver::Version::parse("2.0.0-rc.1").unwrap()));
+        assert!(version_req.matches(&semver::Version::parse("2.1.0-a.1").unwrap()));
+        assert!(!version_req.matches(&semver::Version::parse("2.0.0-a.1").unwrap()));
+    }
+
+    #[test]
+    fn parse_exact_pin() {
+        let version_req = PackageVersionReq::from_specifier("django", "==1.11").unwrap();
+
+        assert!(version_req.matches(&semver::Version::parse("1.11.0").unwrap()));
+        assert!(!version_req.matches(&semver::Version::parse("1.11.2").unwrap()));
+        assert_eq!(
+            version_req.pinned_version(),
+            Some(&semver::Version::parse("1.11.0").unwrap())
+        );
+    }
+
     #[test]
     fn parse_major_only() {
         let requires_txt_line = "django<2";
@@ -0,0 +1,100 @@
+use tags::Tag;
+use errors::*;
+
+/// The components of a wheel filename as described in PEP 427:
+/// `{name}-{version}(-{build tag})?-{python tag}-{abi tag}-{platform tag}.whl`,
+/// where each tag may be a `.` separated set.
+#[derive(Debug, PartialEq)]
+pub struct WheelFilename {
+    pub name: String,
+    pub version: String,
+    pub build_tag: Option<String>,
+    pub python_tags: Vec<String>,
+    pub abi_tags: Vec<String>,
+    pub platform_tags: Vec<String>,
+}
+impl WheelFilename {
+    pub fn parse(filename: &str) -> Result<WheelFilename> {
+        let invalid = || ErrorKind::InvalidWheelFilename(filename.to_owned());
+        if !filename.ends_with(".whl") {
+            bail!(invalid());
+        }
+        let parts: Vec<&str> = filename[..filename.len() - ".whl".len()].split('-').collect();
+        let (name, version, build_tag, tags) = match parts.len() {
+            5 => (parts[0], parts[1], None, &parts[2..]),
+            6 => (parts[0], parts[1], Some(parts[2]), &parts[3..]),
+            _ => bail!(invalid()),
+        };
+        if build_tag.map_or(false, |x| !x.starts_with(|c: char| c.is_ascii_digit())) {
+            bail!(invalid());
+        }
+        let split_tag = |tag: &str| tag.split('.').map(|x| x.to_owned()).collect();
+        Ok(WheelFilename {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            build_tag: build_tag.map(|x| x.to_owned()),
+            python_tags: split_tag(tags[0]),
+            abi_tags: split_tag(tags[1]),
+            platform_tags: split_tag(tags[2]),
+        })
+    }
+
+    /// Every tag this wheel declares support for, expanding compressed tag sets.
+    pub fn tags(&self) -> Vec<Tag> {
+        let mut tags = vec![];
+        for python_tag in self.python_tags.iter() {
+            for abi_tag in self.abi_tags.iter() {
+                for platform_tag in self.platform_tags.iter() {
+                    tags.push(Tag::new(python_tag, abi_tag, platform_tag));
+                }
+            }
+        }
+        tags
+    }
+
+    /// The position of this wheel's best tag in `supported_tags`, which is
+    /// ordered most preferred first. `None` if the wheel isn't installable.
+    pub fn priority(&self, supported_tags: &[Tag]) -> Option<usize> {
+        let tags = self.tags();
+        supported_tags.iter().position(|tag| tags.contains(tag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tags::Tag;
+    use wheel::*;
+
+    #[test]
+    fn parse_compressed_tag_sets() {
+        let wheel = WheelFilename::parse(
+            "numpy-1.13.3-cp36-cp36m-manylinux1_x86_64.manylinux2010_x86_64.whl",
+        ).unwrap();
+
+        assert_eq!(wheel.name, "numpy");
+        assert_eq!(wheel.version, "1.13.3");
+        assert_eq!(wheel.build_tag, None);
+        assert_eq!(
+            wheel.tags(),
+            vec![
+                Tag::new("cp36", "cp36m", "manylinux1_x86_64"),
+                Tag::new("cp36", "cp36m", "manylinux2010_x86_64"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_build_tag() {
+        let wheel = WheelFilename::parse("requests-2.18.4-1-py2.py3-none-any.whl").unwrap();
+
+        assert_eq!(wheel.build_tag, Some("1".to_owned()));
+        assert_eq!(wheel.python_tags, vec!["py2".to_owned(), "py3".to_owned()]);
+    }
+
+    #[test]
+    fn reject_invalid_filenames() {
+        assert!(WheelFilename::parse("requests-2.18.4.tar.gz").is_err());
+        assert!(WheelFilename::parse("requests-2.18.4-any.whl").is_err());
+        assert!(WheelFilename::parse("requests-2.18.4-x1-py3-none-any.whl").is_err());
+    }
+}